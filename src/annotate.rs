@@ -0,0 +1,172 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{
+    line_slice::LineSlice,
+    summary::{Line, Totals},
+};
+
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// one row per file followed by the overall coverage
+pub fn summary(out: &mut impl Write, files: &[(&Path, Totals)]) -> io::Result<()> {
+    let mut all = Totals::default();
+
+    for (path, totals) in files {
+        writeln!(
+            out,
+            "{:>6.2}% {:>5}/{:<5} {}",
+            totals.percent(),
+            totals.covered,
+            totals.total,
+            path.display()
+        )?;
+
+        all += *totals;
+    }
+
+    writeln!(
+        out,
+        "{:>6.2}% {:>5}/{:<5} total",
+        all.percent(),
+        all.covered,
+        all.total
+    )
+}
+
+/// the source of a file with hit counts in the margin, uncovered lines marked with `!`
+/// and lines with untaken branches with `~`
+pub fn listing(
+    out: &mut impl Write,
+    path: &Path,
+    content: &[u8],
+    lines: &BTreeMap<u32, Line>,
+    color: bool,
+) -> io::Result<()> {
+    writeln!(out, "{}", path.display())?;
+
+    for (i, slice) in LineSlice::build(content).iter().enumerate() {
+        let number = i as u32 + 1;
+        let text = String::from_utf8_lossy(&content[slice.start..slice.end]);
+
+        let Some(line) = lines.get(&number) else {
+            writeln!(out, "{number:>6} {:>7}  | {text}", "")?;
+            continue;
+        };
+
+        let (mark, highlight) = if !line.covered {
+            ('!', RED)
+        } else if !line.untaken.is_empty() {
+            ('~', YELLOW)
+        } else {
+            (' ', "")
+        };
+
+        let hits = line.hits.map(|hits| hits.to_string()).unwrap_or_default();
+        let (start, end) = match (color, highlight.is_empty()) {
+            (true, false) => (highlight, RESET),
+            _ => ("", ""),
+        };
+
+        writeln!(out, "{start}{number:>6} {hits:>7} {mark}| {text}{end}")?;
+
+        for untaken in &line.untaken {
+            writeln!(out, "{:>16}| {start}branch never taken: {untaken}{end}", "")?;
+        }
+    }
+
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        coverage::{LogicState, Stats, Trace},
+        summary::lines,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_listing() {
+        let traces = vec![
+            Trace::new(1, Stats::Line(2)),
+            Trace::new(2, Stats::Line(1)),
+            Trace::new(
+                2,
+                Stats::Branch(LogicState {
+                    been_true: true,
+                    been_false: false,
+                }),
+            ),
+            Trace::new(3, Stats::Line(0)),
+        ];
+        let lines = lines(&traces);
+
+        assert_eq!(
+            Totals::of(lines.values()),
+            Totals {
+                covered: 2,
+                total: 3
+            }
+        );
+
+        let mut out = Vec::new();
+        listing(
+            &mut out,
+            Path::new("src/lib.rs"),
+            b"fn a() {\n    if b {\n        c();\n    }\n}\n",
+            &lines,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "src/lib.rs
+     1       2  | fn a() {
+     2       1 ~|     if b {
+                | branch never taken: condition was never false
+     3       0 !|         c();
+     4          |     }
+     5          | }
+
+"
+        );
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut out = Vec::new();
+        summary(
+            &mut out,
+            &[
+                (
+                    Path::new("src/a.rs"),
+                    Totals {
+                        covered: 1,
+                        total: 4,
+                    },
+                ),
+                (
+                    Path::new("src/b.rs"),
+                    Totals {
+                        covered: 3,
+                        total: 4,
+                    },
+                ),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            " 25.00%     1/4     src/a.rs\n 75.00%     3/4     src/b.rs\n 50.00%     4/8     total\n"
+        );
+    }
+}
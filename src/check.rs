@@ -0,0 +1,491 @@
+use std::{
+    io::{self, Write},
+    os::fd::AsFd,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use serde_json::json;
+use tracing::error;
+
+use crate::{
+    config::{CheckConfig, RunnerConfig},
+    summary::{self, File, FunctionCoverage, Totals},
+};
+
+/// what a threshold was checked against
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scope {
+    Total,
+    File(PathBuf),
+    Function {
+        path: PathBuf,
+        name: String,
+        /// one based, inclusive
+        lines: (u32, u32),
+    },
+}
+
+/// one threshold applied to one scope
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub scope: Scope,
+    pub totals: Totals,
+    pub threshold: f64,
+}
+
+impl Check {
+    pub fn passed(&self) -> bool {
+        self.totals.percent() >= self.threshold
+    }
+
+    fn describe(&self) -> String {
+        let subject = match &self.scope {
+            Scope::Total => "total coverage".to_string(),
+            Scope::File(path) => format!("coverage of {}", path.display()),
+            Scope::Function { name, .. } => format!("coverage of `{name}`"),
+        };
+
+        let verdict = if self.passed() { "meets" } else { "is below" };
+
+        format!(
+            "{subject} {:.2}% ({}/{}) {verdict} {:.2}%",
+            self.totals.percent(),
+            self.totals.covered,
+            self.totals.total,
+            self.threshold
+        )
+    }
+
+    fn location(&self) -> Option<(&Path, Option<(u32, u32)>)> {
+        match &self.scope {
+            Scope::Total => None,
+            Scope::File(path) => Some((path, None)),
+            Scope::Function { path, lines, .. } => Some((path, Some(*lines))),
+        }
+    }
+}
+
+/// runs the backend to completion, its output goes to stderr to keep stdout for the results
+pub fn run(runner: &RunnerConfig, target_dir: &Path) -> eyre::Result<()> {
+    let stderr = io::stderr().as_fd().try_clone_to_owned()?;
+
+    let status = runner
+        .command(target_dir, None, false)
+        .stdin(Stdio::null())
+        .stdout(Stdio::from(stderr))
+        .status()?;
+
+    eyre::ensure!(status.success(), "coverage run exited with {status}");
+
+    Ok(())
+}
+
+/// applies each configured threshold to every scope it covers
+pub fn evaluate(files: &[File], thresholds: &CheckConfig) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    if let Some(threshold) = thresholds.function {
+        for file in files {
+            let functions = match summary::functions(&file.content, &file.lines) {
+                Ok(functions) => functions,
+                Err(error) => {
+                    error!(%error, path = %file.path.display(), "failed to find functions");
+                    continue;
+                }
+            };
+
+            for FunctionCoverage {
+                function, totals, ..
+            } in functions
+            {
+                let mut name = function.modules.clone();
+                name.push(function.qualified_name());
+
+                checks.push(Check {
+                    scope: Scope::Function {
+                        path: file.path.clone(),
+                        name: name.join("::"),
+                        lines: (function.start.row as u32 + 1, function.end.row as u32 + 1),
+                    },
+                    totals,
+                    threshold,
+                });
+            }
+        }
+    }
+
+    if let Some(threshold) = thresholds.file {
+        for file in files {
+            checks.push(Check {
+                scope: Scope::File(file.path.clone()),
+                totals: Totals::of(file.lines.values()),
+                threshold,
+            });
+        }
+    }
+
+    if let Some(threshold) = thresholds.total {
+        checks.push(Check {
+            scope: Scope::Total,
+            totals: Totals::of(files.iter().flat_map(|file| file.lines.values())),
+            threshold,
+        });
+    }
+
+    checks
+}
+
+pub fn human(out: &mut impl Write, files: &[File], checks: &[Check]) -> io::Result<()> {
+    let totals = files
+        .iter()
+        .map(|file| (file.path.as_path(), Totals::of(file.lines.values())))
+        .collect::<Vec<_>>();
+
+    crate::annotate::summary(out, &totals)?;
+
+    let failed = checks
+        .iter()
+        .filter(|check| !check.passed())
+        .collect::<Vec<_>>();
+    if !failed.is_empty() {
+        writeln!(out)?;
+    }
+
+    for check in &failed {
+        match check.location() {
+            Some((path, Some((line, _)))) => write!(out, "{}:{line}: ", path.display())?,
+            Some((path, None)) => write!(out, "{}: ", path.display())?,
+            None => (),
+        }
+
+        writeln!(out, "{}", check.describe())?;
+    }
+
+    writeln!(out)?;
+    if failed.is_empty() {
+        writeln!(out, "coverage check passed")
+    } else {
+        writeln!(
+            out,
+            "coverage check failed: {} of {} thresholds not met",
+            failed.len(),
+            checks.len()
+        )
+    }
+}
+
+/// `::warning` for uncovered lines and `::error` for missed thresholds
+pub fn github(out: &mut impl Write, files: &[File], checks: &[Check]) -> io::Result<()> {
+    for file in files {
+        for (first, last) in summary::uncovered(&file.lines) {
+            writeln!(
+                out,
+                "::warning file={},line={first},endLine={last},title={}::{}",
+                github_property(&file.path.to_string_lossy()),
+                github_property("not covered"),
+                github_data("not covered by tests")
+            )?;
+        }
+    }
+
+    for check in checks.iter().filter(|check| !check.passed()) {
+        let mut properties = Vec::new();
+
+        if let Some((path, lines)) = check.location() {
+            properties.push(format!("file={}", github_property(&path.to_string_lossy())));
+
+            if let Some((first, last)) = lines {
+                properties.push(format!("line={first}"));
+                properties.push(format!("endLine={last}"));
+            }
+        }
+
+        properties.push(format!("title={}", github_property("coverage threshold")));
+
+        writeln!(
+            out,
+            "::error {}::{}",
+            properties.join(","),
+            github_data(&check.describe())
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn sarif(out: &mut impl Write, files: &[File], checks: &[Check]) -> io::Result<()> {
+    let mut results = Vec::new();
+
+    for file in files {
+        for (first, last) in summary::uncovered(&file.lines) {
+            results.push(json!({
+                "ruleId": "uncovered",
+                "level": "warning",
+                "message": { "text": "not covered by tests" },
+                "locations": [sarif_location(&file.path, Some((first, last)))],
+            }));
+        }
+    }
+
+    for check in checks.iter().filter(|check| !check.passed()) {
+        let locations = check
+            .location()
+            .map(|(path, lines)| sarif_location(path, lines))
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        results.push(json!({
+            "ruleId": "coverage-threshold",
+            "level": "error",
+            "message": { "text": check.describe() },
+            "locations": locations,
+        }));
+    }
+
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "tarballin",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": [
+                        {
+                            "id": "uncovered",
+                            "shortDescription": { "text": "Code not covered by tests" },
+                        },
+                        {
+                            "id": "coverage-threshold",
+                            "shortDescription": { "text": "Coverage below the configured threshold" },
+                        },
+                    ],
+                },
+            },
+            "results": results,
+        }],
+    });
+
+    serde_json::to_writer_pretty(&mut *out, &log)?;
+    writeln!(out)
+}
+
+/// a test case per check, failed when the threshold is not met
+pub fn junit(out: &mut impl Write, checks: &[Check]) -> io::Result<()> {
+    let failures = checks.iter().filter(|check| !check.passed()).count();
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<testsuites name="tarballin" tests="{}" failures="{failures}">"#,
+        checks.len()
+    )?;
+    writeln!(
+        out,
+        r#"  <testsuite name="coverage" tests="{}" failures="{failures}">"#,
+        checks.len()
+    )?;
+
+    for check in checks {
+        let (classname, name, file) = match &check.scope {
+            Scope::Total => ("coverage.total", "total".to_string(), None),
+            Scope::File(path) => (
+                "coverage.file",
+                path.display().to_string(),
+                Some(path.display().to_string()),
+            ),
+            Scope::Function { path, name, .. } => (
+                "coverage.function",
+                format!("{}::{name}", path.display()),
+                Some(path.display().to_string()),
+            ),
+        };
+
+        let file = file
+            .map(|file| format!(r#" file="{}""#, xml_escape(&file)))
+            .unwrap_or_default();
+
+        write!(
+            out,
+            r#"    <testcase classname="{classname}" name="{}"{file}"#,
+            xml_escape(&name)
+        )?;
+
+        if check.passed() {
+            writeln!(out, "/>")?;
+        } else {
+            writeln!(out, ">")?;
+            writeln!(
+                out,
+                r#"      <failure message="{}"/>"#,
+                xml_escape(&check.describe())
+            )?;
+            writeln!(out, "    </testcase>")?;
+        }
+    }
+
+    writeln!(out, "  </testsuite>")?;
+    writeln!(out, "</testsuites>")
+}
+
+fn sarif_location(path: &Path, lines: Option<(u32, u32)>) -> serde_json::Value {
+    let mut location = json!({
+        "physicalLocation": {
+            "artifactLocation": {
+                "uri": path.to_string_lossy(),
+                "uriBaseId": "%SRCROOT%",
+            },
+        },
+    });
+
+    if let Some((first, last)) = lines {
+        location["physicalLocation"]["region"] = json!({
+            "startLine": first,
+            "endLine": last,
+        });
+    }
+
+    location
+}
+
+fn github_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn github_property(text: &str) -> String {
+    github_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::coverage::{Stats, Trace};
+
+    use super::*;
+
+    fn files() -> Vec<File> {
+        let traces = vec![
+            Trace::new(1, Stats::Line(1)),
+            Trace::new(2, Stats::Line(1)),
+            Trace::new(5, Stats::Line(0)),
+            Trace::new(6, Stats::Line(0)),
+        ];
+
+        vec![File {
+            path: PathBuf::from("src/lib.rs"),
+            content: b"fn a() {\n    b();\n}\n\nfn b() {\n    todo!()\n}\n".to_vec(),
+            lines: summary::lines(&traces),
+        }]
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let checks = evaluate(
+            &files(),
+            &CheckConfig {
+                total: Some(50.0),
+                file: None,
+                function: Some(80.0),
+            },
+        );
+
+        let summary = checks
+            .iter()
+            .map(|check| (check.scope.clone(), check.passed()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    Scope::Function {
+                        path: PathBuf::from("src/lib.rs"),
+                        name: "a".to_string(),
+                        lines: (1, 3),
+                    },
+                    true
+                ),
+                (
+                    Scope::Function {
+                        path: PathBuf::from("src/lib.rs"),
+                        name: "b".to_string(),
+                        lines: (5, 7),
+                    },
+                    false
+                ),
+                (Scope::Total, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_github() {
+        let files = files();
+        let checks = evaluate(
+            &files,
+            &CheckConfig {
+                total: Some(75.0),
+                ..CheckConfig::default()
+            },
+        );
+
+        let mut out = Vec::new();
+        github(&mut out, &files, &checks).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "::warning file=src/lib.rs,line=5,endLine=6,title=not covered::not covered by tests\n\
+             ::error title=coverage threshold::total coverage 50.00%25 (2/4) is below 75.00%25\n"
+        );
+    }
+
+    #[test]
+    fn test_junit() {
+        let checks = evaluate(
+            &files(),
+            &CheckConfig {
+                file: Some(50.0),
+                function: Some(50.0),
+                ..CheckConfig::default()
+            },
+        );
+
+        let mut out = Vec::new();
+        junit(&mut out, &checks).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains(r#"<testsuite name="coverage" tests="3" failures="1">"#));
+        assert!(out.contains(
+            r#"<testcase classname="coverage.function" name="src/lib.rs::b" file="src/lib.rs">"#
+        ));
+        assert!(out.contains(
+            r#"<testcase classname="coverage.file" name="src/lib.rs" file="src/lib.rs"/>"#
+        ));
+    }
+
+    #[test]
+    fn test_sarif() {
+        let files = files();
+        let mut out = Vec::new();
+        sarif(&mut out, &files, &[]).unwrap();
+
+        let log: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let results = log["runs"][0]["results"].as_array().unwrap();
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["region"],
+            json!({ "startLine": 5, "endLine": 6 })
+        );
+    }
+}
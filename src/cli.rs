@@ -1,23 +1,140 @@
-use std::{fs::File, path::PathBuf, str::FromStr};
+use std::{
+    fs::File,
+    io::{self, IsTerminal},
+    path::PathBuf,
+    str::FromStr,
+};
 
+use lsp_server::Connection;
 use tracing::Level;
 use tracing_subscriber::util::SubscriberInitExt;
 
+use crate::{
+    coverage::Format,
+    transport::{self, IoThreads},
+};
+
 #[derive(clap::Parser)]
+#[clap(args_conflicts_with_subcommands = true)]
 pub struct Args {
-    /// how to connect to an editor
-    #[clap(short, long)]
-    pub connect: Conn,
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// without a subcommand the language server is started
+    #[clap(flatten)]
+    pub serve: Serve,
 
     /// override the log location
-    #[clap(short, long)]
+    #[clap(short, long, global = true)]
     pub log: Option<PathBuf>,
 
     /// override the log level
-    #[clap(short, long)]
+    #[clap(short = 'L', long, global = true)]
     pub level: Option<Level>,
 }
 
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// run the language server, the default
+    Serve(Serve),
+
+    /// print coverage with the ignore rules applied
+    Report(Report),
+
+    /// fail when coverage with the ignore rules applied is below the thresholds
+    Check(Check),
+
+    /// remove the persistent coverage target directory
+    Clean,
+}
+
+#[derive(clap::Args, Default)]
+pub struct Serve {
+    /// how to connect to an editor
+    ///
+    /// "-" for stdio, "tcp://HOST:PORT" or "unix:PATH" to listen for an editor,
+    /// prefix with "connect:" to connect to a listening editor instead
+    #[clap(short, long, default_value = "-")]
+    pub connect: Conn,
+
+    /// serve an existing coverage file instead of running cargo, reloading it when it changes
+    #[clap(long, value_name = "PATH")]
+    pub coverage: Option<PathBuf>,
+}
+
+/// where the commands outside the language server read coverage from
+#[derive(clap::Args)]
+pub struct Input {
+    /// the coverage file to read, by default the one the configured backend writes
+    #[clap(long, value_name = "PATH")]
+    pub coverage: Option<PathBuf>,
+
+    /// the format of the coverage file
+    #[clap(long, value_enum)]
+    pub format: Option<Format>,
+}
+
+#[derive(clap::Args)]
+pub struct Report {
+    /// only show these files, all covered files by default
+    pub files: Vec<PathBuf>,
+
+    #[clap(flatten)]
+    pub input: Input,
+
+    /// print the per file summary without the annotated sources
+    #[clap(long)]
+    pub summary: bool,
+
+    /// when to highlight uncovered lines
+    #[clap(long, value_enum, default_value_t = Color::Auto)]
+    pub color: Color,
+}
+
+#[derive(clap::Args)]
+pub struct Check {
+    /// run the configured backend first instead of reading what the last run left
+    #[clap(long)]
+    pub run: bool,
+
+    #[clap(flatten)]
+    pub input: Input,
+
+    #[clap(long, value_enum, default_value_t = Output::Human)]
+    pub output: Output,
+
+    /// minimum total coverage in percent, overrides the configuration
+    #[clap(long, value_name = "PERCENT")]
+    pub total: Option<f64>,
+
+    /// minimum coverage of every file in percent
+    #[clap(long, value_name = "PERCENT")]
+    pub file: Option<f64>,
+
+    /// minimum coverage of every function in percent
+    #[clap(long, value_name = "PERCENT")]
+    pub function: Option<f64>,
+}
+
+/// how check results are written
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Output {
+    Human,
+    /// SARIF 2.1.0 for code scanning
+    Sarif,
+    /// workflow command annotations for GitHub Actions
+    Github,
+    /// JUnit XML with a test case per threshold
+    Junit,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
 impl Args {
     pub fn setup_subscriber(&self) {
         match (self.log.as_ref(), self.level.as_ref()) {
@@ -74,6 +191,53 @@ impl Args {
 pub enum Conn {
     #[default]
     Stdio,
+    TcpListen(String),
+    TcpConnect(String),
+    UnixListen(PathBuf),
+    UnixConnect(PathBuf),
+}
+
+impl Conn {
+    pub fn open(&self) -> io::Result<(Connection, IoThreads)> {
+        match self {
+            Conn::Stdio => {
+                let (conn, threads) = Connection::stdio();
+                Ok((conn, threads.into()))
+            }
+            Conn::TcpListen(addr) => {
+                let (conn, threads) = Connection::listen(addr)?;
+                Ok((conn, threads.into()))
+            }
+            Conn::TcpConnect(addr) => {
+                let (conn, threads) = Connection::connect(addr)?;
+                Ok((conn, threads.into()))
+            }
+            Conn::UnixListen(path) => transport::unix_listen(path),
+            Conn::UnixConnect(path) => transport::unix_connect(path),
+        }
+    }
+}
+
+impl Color {
+    pub fn enabled(self) -> bool {
+        match self {
+            Color::Auto => std::io::stdout().is_terminal(),
+            Color::Always => true,
+            Color::Never => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Conn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Conn::Stdio => write!(f, "stdio"),
+            Conn::TcpListen(addr) => write!(f, "tcp://{addr}"),
+            Conn::TcpConnect(addr) => write!(f, "connect:tcp://{addr}"),
+            Conn::UnixListen(path) => write!(f, "unix:{}", path.display()),
+            Conn::UnixConnect(path) => write!(f, "connect:unix:{}", path.display()),
+        }
+    }
 }
 
 impl FromStr for Conn {
@@ -84,6 +248,34 @@ impl FromStr for Conn {
             return Ok(Self::Stdio);
         }
 
+        let (connect, rest) = match s.strip_prefix("connect:") {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+
+        if let Some(addr) = rest.strip_prefix("tcp://") {
+            if !addr.is_empty() {
+                let addr = addr.to_string();
+                return Ok(if connect {
+                    Self::TcpConnect(addr)
+                } else {
+                    Self::TcpListen(addr)
+                });
+            }
+        }
+
+        if let Some(path) = rest.strip_prefix("unix:") {
+            let path = path.strip_prefix("//").unwrap_or(path);
+            if !path.is_empty() {
+                let path = PathBuf::from(path);
+                return Ok(if connect {
+                    Self::UnixConnect(path)
+                } else {
+                    Self::UnixListen(path)
+                });
+            }
+        }
+
         Err(InvalidConnection(s.to_string()))
     }
 }
@@ -99,7 +291,85 @@ impl std::fmt::Display for InvalidConnection {
     }
 }
 
+#[test]
+fn test_args() {
+    use clap::CommandFactory;
+
+    Args::command().debug_assert();
+}
+
+#[test]
+fn test_subcommands() {
+    use clap::Parser;
+
+    let args = Args::try_parse_from(["tarballin", "--connect", "tcp://127.0.0.1:9257"]).unwrap();
+    assert!(args.command.is_none());
+    assert_eq!(
+        args.serve.connect,
+        Conn::TcpListen("127.0.0.1:9257".to_string())
+    );
+
+    let args = Args::try_parse_from(["tarballin", "report", "src/lib.rs", "--summary"]).unwrap();
+    assert!(matches!(
+        args.command,
+        Some(Command::Report(Report { ref files, summary: true, .. })) if files == &[PathBuf::from("src/lib.rs")]
+    ));
+
+    let args = Args::try_parse_from([
+        "tarballin",
+        "check",
+        "--output",
+        "sarif",
+        "--function",
+        "75",
+    ])
+    .unwrap();
+    assert!(matches!(
+        args.command,
+        Some(Command::Check(Check {
+            output: Output::Sarif,
+            function: Some(percent),
+            ..
+        })) if percent == 75.0
+    ));
+
+    let args = Args::try_parse_from(["tarballin", "clean", "-L", "debug"]).unwrap();
+    assert!(matches!(args.command, Some(Command::Clean)));
+    assert_eq!(args.level, Some(Level::DEBUG));
+}
+
 #[test]
 fn test_str() {
     assert_eq!(Conn::from_str("-").unwrap(), Conn::Stdio);
 }
+
+#[test]
+fn test_str_tcp() {
+    assert_eq!(
+        Conn::from_str("tcp://127.0.0.1:9257").unwrap(),
+        Conn::TcpListen("127.0.0.1:9257".to_string())
+    );
+    assert_eq!(
+        Conn::from_str("connect:tcp://localhost:9257").unwrap(),
+        Conn::TcpConnect("localhost:9257".to_string())
+    );
+    assert!(Conn::from_str("tcp://").is_err());
+}
+
+#[test]
+fn test_str_unix() {
+    assert_eq!(
+        Conn::from_str("unix:/tmp/tarballin.sock").unwrap(),
+        Conn::UnixListen(PathBuf::from("/tmp/tarballin.sock"))
+    );
+    assert_eq!(
+        Conn::from_str("unix:///tmp/tarballin.sock").unwrap(),
+        Conn::UnixListen(PathBuf::from("/tmp/tarballin.sock"))
+    );
+    assert_eq!(
+        Conn::from_str("connect:unix:run/tarballin.sock").unwrap(),
+        Conn::UnixConnect(PathBuf::from("run/tarballin.sock"))
+    );
+    assert!(Conn::from_str("unix:").is_err());
+    assert!(Conn::from_str("connect:-").is_err());
+}
@@ -0,0 +1,197 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString,
+    Position, Range,
+};
+use serde::Deserialize;
+use url::Url;
+
+/// a line of cargo's `--message-format json` output
+#[derive(Deserialize)]
+struct CargoMessage {
+    reason: String,
+    message: Option<CompilerMessage>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CompilerMessage {
+    pub message: String,
+    pub level: String,
+    pub code: Option<Code>,
+    #[serde(default)]
+    pub spans: Vec<Span>,
+    #[serde(default)]
+    pub children: Vec<CompilerMessage>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Code {
+    pub code: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Span {
+    pub file_name: PathBuf,
+    pub line_start: u32,
+    pub line_end: u32,
+    pub column_start: u32,
+    pub column_end: u32,
+    pub is_primary: bool,
+    pub label: Option<String>,
+}
+
+/// picks the compiler errors out of a run's output, anything else is ignored
+pub fn errors(output: &str) -> Vec<CompilerMessage> {
+    output
+        .lines()
+        .filter(|line| line.starts_with('{'))
+        .filter_map(|line| serde_json::from_str::<CargoMessage>(line).ok())
+        .filter(|msg| msg.reason == "compiler-message")
+        .filter_map(|msg| msg.message)
+        .filter(|msg| msg.level.starts_with("error"))
+        .collect()
+}
+
+/// turns compiler messages into diagnostics grouped by the file of their primary span
+///
+/// relative span paths are taken relative to `root`, where cargo was run
+pub fn diagnostics(messages: &[CompilerMessage], root: &Path) -> HashMap<PathBuf, Vec<Diagnostic>> {
+    let mut diags = HashMap::<PathBuf, Vec<Diagnostic>>::new();
+
+    for msg in messages {
+        let Some(primary) = msg.spans.iter().find(|span| span.is_primary) else {
+            continue;
+        };
+
+        let mut message = msg.message.clone();
+        let mut related = Vec::new();
+
+        for span in msg.spans.iter().filter(|span| !span.is_primary) {
+            if let (Some(label), Some(location)) = (&span.label, span.location(root)) {
+                related.push(DiagnosticRelatedInformation {
+                    location,
+                    message: label.clone(),
+                });
+            }
+        }
+
+        for child in &msg.children {
+            match child.spans.iter().find_map(|span| span.location(root)) {
+                Some(location) => related.push(DiagnosticRelatedInformation {
+                    location,
+                    message: format!("{}: {}", child.level, child.message),
+                }),
+                None => message.push_str(&format!("\n{}: {}", child.level, child.message)),
+            }
+        }
+
+        if let Some(label) = &primary.label {
+            message.push_str(&format!("\n{label}"));
+        }
+
+        diags
+            .entry(resolve(root, &primary.file_name))
+            .or_default()
+            .push(Diagnostic {
+                range: primary.range(),
+                severity: Some(DiagnosticSeverity::ERROR),
+                code: msg
+                    .code
+                    .as_ref()
+                    .map(|code| NumberOrString::String(code.code.clone())),
+                code_description: None,
+                source: Some("tarballin".to_string()),
+                message,
+                related_information: (!related.is_empty()).then_some(related),
+                tags: None,
+                data: None,
+            });
+    }
+
+    diags
+}
+
+impl Span {
+    fn range(&self) -> Range {
+        Range::new(
+            Position {
+                line: self.line_start.saturating_sub(1),
+                character: self.column_start.saturating_sub(1),
+            },
+            Position {
+                line: self.line_end.saturating_sub(1),
+                character: self.column_end.saturating_sub(1),
+            },
+        )
+    }
+
+    fn location(&self, root: &Path) -> Option<Location> {
+        let uri = Url::from_file_path(resolve(root, &self.file_name)).ok()?;
+
+        Some(Location {
+            uri,
+            range: self.range(),
+        })
+    }
+}
+
+fn resolve(root: &Path, path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        root.join(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const OUTPUT: &str = r#"Jan 01 00:00:00.000  INFO cargo_tarpaulin::config: Creating config
+{"reason":"compiler-artifact","package_id":"dep 0.1.0","target":{"name":"dep"},"fresh":true}
+{"reason":"compiler-message","package_id":"demo 0.1.0","message":{"rendered":"warning: unused variable","children":[],"code":null,"level":"warning","message":"unused variable: `y`","spans":[{"file_name":"src/lib.rs","line_start":3,"line_end":3,"column_start":9,"column_end":10,"is_primary":true,"label":null}]}}
+{"reason":"compiler-message","package_id":"demo 0.1.0","message":{"rendered":"error[E0425]","children":[{"children":[],"code":null,"level":"help","message":"a local variable with a similar name exists","spans":[{"file_name":"src/lib.rs","line_start":2,"line_end":2,"column_start":5,"column_end":6,"is_primary":true,"label":null}]},{"children":[],"code":null,"level":"note","message":"required by a bound","spans":[]}],"code":{"code":"E0425","explanation":null},"level":"error","message":"cannot find value `x` in this scope","spans":[{"file_name":"src/lib.rs","line_start":4,"line_end":4,"column_start":13,"column_end":14,"is_primary":true,"label":"not found in this scope"}]}}
+{"reason":"compiler-message","package_id":"demo 0.1.0","message":{"rendered":"error: aborting","children":[],"code":null,"level":"error","message":"aborting due to 1 previous error","spans":[]}}
+{"reason":"build-finished","success":false}
+"#;
+
+    #[test]
+    fn test_errors() {
+        let errors = errors(OUTPUT);
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].message, "cannot find value `x` in this scope");
+        assert_eq!(errors[1].message, "aborting due to 1 previous error");
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let root = Path::new("/work/demo");
+        let diags = diagnostics(&errors(OUTPUT), root);
+
+        assert_eq!(diags.len(), 1);
+
+        let file = &diags[Path::new("/work/demo/src/lib.rs")];
+        assert_eq!(file.len(), 1);
+
+        let diag = &file[0];
+        assert_eq!(
+            diag.range,
+            Range::new(Position::new(3, 12), Position::new(3, 13))
+        );
+        assert_eq!(diag.code, Some(NumberOrString::String("E0425".to_string())));
+        assert_eq!(diag.source.as_deref(), Some("tarballin"));
+        assert_eq!(
+            diag.message,
+            "cannot find value `x` in this scope\nnote: required by a bound\nnot found in this scope"
+        );
+
+        let related = diag.related_information.as_ref().unwrap();
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].location.range.start, Position::new(1, 4));
+    }
+}
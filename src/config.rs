@@ -0,0 +1,647 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::debug;
+
+use crate::{
+    coverage::{Format, Source},
+    group::Grouping,
+};
+
+/// project files consulted for configuration, later files take precedence
+const PROJECT_FILES: [&str; 2] = ["tarballin.toml", ".tarballin.toml"];
+
+#[derive(Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// where coverage builds are kept between sessions, defaults to `target/tarballin`
+    pub target_dir: Option<PathBuf>,
+    pub runner: RunnerConfig,
+    pub coverage: CoverageConfig,
+    pub check: CheckConfig,
+    pub diff: DiffConfig,
+    pub diagnostics: DiagnosticsConfig,
+    pub hints: HintsConfig,
+    pub index: IndexConfig,
+}
+
+/// the line to tests index, built in the background by running every test on its own
+#[derive(Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct IndexConfig {
+    pub enabled: bool,
+}
+
+/// which lines get an inlay hint with their execution count
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct HintsConfig {
+    pub show: Hints,
+    /// lines hit at least this often are hot, in the heatmap as well
+    pub hot: usize,
+    /// lines hit at most this often are cold
+    pub cold: usize,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Hints {
+    #[default]
+    All,
+    Hot,
+    Cold,
+}
+
+/// how coverage is turned into diagnostics
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct DiagnosticsConfig {
+    pub group: Grouping,
+    /// an information diagnostic on every function with its line coverage
+    pub functions: bool,
+    /// what the code actions put on items to exclude them from coverage
+    pub attribute: String,
+}
+
+/// limits diagnostics to what changed
+#[derive(Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct DiffConfig {
+    /// a git revision like `origin/main` or `HEAD`, lines changed since are the only ones flagged
+    pub base: Option<String>,
+}
+
+/// minimum coverage in percent for `tarballin check`, unset thresholds are not checked
+#[derive(Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct CheckConfig {
+    pub total: Option<f64>,
+    pub file: Option<f64>,
+    pub function: Option<f64>,
+}
+
+/// where coverage is read from once a run finishes
+#[derive(Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct CoverageConfig {
+    /// a report to read instead of tarpaulin's own, e.g. one written with `--out Lcov`
+    pub path: Option<PathBuf>,
+    pub format: Format,
+    /// never run cargo, only watch the report for changes made by someone else
+    pub offline: bool,
+}
+
+/// how the coverage tool gets invoked
+#[derive(Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RunnerConfig {
+    pub backend: Backend,
+    pub features: Vec<String>,
+    pub all_features: bool,
+    pub no_default_features: bool,
+    pub workspace: bool,
+    pub packages: Vec<String>,
+    /// tarpaulin only
+    pub engine: Option<Engine>,
+    pub skip_clean: bool,
+    pub lib: bool,
+    pub tests: bool,
+    /// per test timeout in seconds, tarpaulin only
+    pub timeout: Option<u64>,
+    /// extra arguments passed to the backend verbatim
+    pub args: Vec<String>,
+    pub env: HashMap<String, String>,
+}
+
+/// the cargo subcommand that collects coverage
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    #[default]
+    Tarpaulin,
+    /// `cargo llvm-cov`, which reports regions with their columns
+    LlvmCov,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    Auto,
+    Ptrace,
+    Llvm,
+}
+
+impl Default for DiagnosticsConfig {
+    fn default() -> Self {
+        DiagnosticsConfig {
+            group: Grouping::default(),
+            functions: true,
+            attribute: "#[cfg_attr(coverage_nightly, coverage(off))]".to_string(),
+        }
+    }
+}
+
+impl Default for HintsConfig {
+    fn default() -> Self {
+        HintsConfig {
+            show: Hints::default(),
+            hot: 1000,
+            cold: 0,
+        }
+    }
+}
+
+impl HintsConfig {
+    pub fn shows(&self, hits: usize) -> bool {
+        match self.show {
+            Hints::All => true,
+            Hints::Hot => hits >= self.hot,
+            Hints::Cold => hits <= self.cold,
+        }
+    }
+}
+
+impl Config {
+    /// loads the project configuration with the editor's initialization options layered on top
+    pub fn load(root: &Path, options: Option<&Value>) -> eyre::Result<Self> {
+        let mut value = Value::Object(Default::default());
+
+        for name in PROJECT_FILES {
+            let path = root.join(name);
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+
+            debug!(path = %path.display(), "loading project config");
+            let project: toml::Value = toml::from_str(&content)?;
+            merge(&mut value, serde_json::to_value(project)?);
+        }
+
+        if let Some(options) = options.filter(|options| !options.is_null()) {
+            merge(&mut value, options.clone());
+        }
+
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+impl Config {
+    pub fn target_dir(&self) -> PathBuf {
+        match &self.target_dir {
+            Some(dir) => dir.clone(),
+            None => PathBuf::from("target").join("tarballin"),
+        }
+    }
+
+    /// where finished runs leave their coverage, a configured report wins over the backend's own
+    pub fn source(&self, package: String, target_dir: &Path, root: &Path) -> Source {
+        if let Some(path) = &self.coverage.path {
+            return Source::File {
+                path: root.join(path),
+                format: self.coverage.format,
+                root: root.to_path_buf(),
+            };
+        }
+
+        match self.runner.backend {
+            Backend::Tarpaulin => Source::Tarpaulin {
+                package,
+                target: target_dir.to_path_buf(),
+            },
+            Backend::LlvmCov => Source::File {
+                path: Backend::llvm_cov_report(target_dir),
+                format: Format::LlvmCov,
+                root: root.to_path_buf(),
+            },
+        }
+    }
+}
+
+impl RunnerConfig {
+    /// the backend invocation, `filter` limits the run to the tests whose name contains it
+    ///
+    /// `messages` has cargo print compiler messages as json, for build errors to be reported
+    pub fn command(&self, target_dir: &Path, filter: Option<&str>, messages: bool) -> Command {
+        let mut cmd = Command::new("cargo");
+
+        match self.backend {
+            Backend::Tarpaulin => cmd.arg("tarpaulin"),
+            Backend::LlvmCov => cmd
+                .args(["llvm-cov", "--json", "--output-path"])
+                .arg(Backend::llvm_cov_report(target_dir)),
+        };
+
+        cmd.arg("--target-dir").arg(target_dir);
+
+        if messages {
+            cmd.args(["--message-format", "json"]);
+        }
+
+        cmd.args(self.args()).envs(&self.env);
+
+        if let Some(filter) = filter {
+            // test binary arguments may already have been started by the extra args
+            if !self.args.iter().any(|arg| arg == "--") {
+                cmd.arg("--");
+            }
+
+            cmd.arg(filter);
+        }
+
+        cmd
+    }
+
+    /// `cargo test` listing the tests the backend would run
+    pub fn list_command(&self, target_dir: &Path) -> Command {
+        let mut cmd = Command::new("cargo");
+
+        cmd.arg("test").arg("--target-dir").arg(target_dir);
+
+        if !self.features.is_empty() {
+            cmd.arg("--features").arg(self.features.join(","));
+        }
+
+        if self.all_features {
+            cmd.arg("--all-features");
+        }
+
+        if self.no_default_features {
+            cmd.arg("--no-default-features");
+        }
+
+        if self.workspace {
+            cmd.arg("--workspace");
+        }
+
+        for package in &self.packages {
+            cmd.arg("--package").arg(package);
+        }
+
+        if self.lib {
+            cmd.arg("--lib");
+        }
+
+        if self.tests {
+            cmd.arg("--tests");
+        }
+
+        cmd.args(["--", "--list", "--format", "terse"])
+            .envs(&self.env);
+
+        cmd
+    }
+
+    fn args(&self) -> Vec<String> {
+        match self.backend {
+            Backend::Tarpaulin => self.tarpaulin_args(),
+            Backend::LlvmCov => self.llvm_cov_args(),
+        }
+    }
+
+    fn llvm_cov_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if !self.features.is_empty() {
+            args.push("--features".to_string());
+            args.push(self.features.join(","));
+        }
+
+        if self.all_features {
+            args.push("--all-features".to_string());
+        }
+
+        if self.no_default_features {
+            args.push("--no-default-features".to_string());
+        }
+
+        if self.workspace {
+            args.push("--workspace".to_string());
+        }
+
+        for package in &self.packages {
+            args.push("--package".to_string());
+            args.push(package.clone());
+        }
+
+        if self.skip_clean {
+            args.push("--no-clean".to_string());
+        }
+
+        if self.lib {
+            args.push("--lib".to_string());
+        }
+
+        if self.tests {
+            args.push("--tests".to_string());
+        }
+
+        if self.engine.is_some() || self.timeout.is_some() {
+            debug!("engine and timeout only apply to tarpaulin");
+        }
+
+        args.extend(self.args.iter().cloned());
+
+        args
+    }
+
+    fn tarpaulin_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if !self.features.is_empty() {
+            args.push("--features".to_string());
+            args.push(self.features.join(","));
+        }
+
+        if self.all_features {
+            args.push("--all-features".to_string());
+        }
+
+        if self.no_default_features {
+            args.push("--no-default-features".to_string());
+        }
+
+        if self.workspace {
+            args.push("--workspace".to_string());
+        }
+
+        for package in &self.packages {
+            args.push("--packages".to_string());
+            args.push(package.clone());
+        }
+
+        if let Some(engine) = self.engine {
+            args.push("--engine".to_string());
+            args.push(engine.as_str().to_string());
+        }
+
+        if self.skip_clean {
+            args.push("--skip-clean".to_string());
+        }
+
+        if self.lib {
+            args.push("--lib".to_string());
+        }
+
+        if self.tests {
+            args.push("--tests".to_string());
+        }
+
+        if let Some(timeout) = self.timeout {
+            args.push("--timeout".to_string());
+            args.push(timeout.to_string());
+        }
+
+        args.extend(self.args.iter().cloned());
+
+        args
+    }
+}
+
+impl Backend {
+    fn llvm_cov_report(target_dir: &Path) -> PathBuf {
+        target_dir.join("llvm-cov.json")
+    }
+}
+
+impl Engine {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Engine::Auto => "auto",
+            Engine::Ptrace => "ptrace",
+            Engine::Llvm => "llvm",
+        }
+    }
+}
+
+/// recursively merges objects, anything else in `over` replaces what is in `base`
+fn merge(base: &mut Value, over: Value) {
+    match (base, over) {
+        (Value::Object(base), Value::Object(over)) => {
+            for (key, value) in over {
+                merge(base.entry(key).or_insert(Value::Null), value);
+            }
+        }
+
+        (base, over) => *base = over,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_args() {
+        assert!(RunnerConfig::default().args().is_empty());
+    }
+
+    fn argv(command: &Command) -> Vec<String> {
+        command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_command() {
+        let tarpaulin = RunnerConfig {
+            workspace: true,
+            ..RunnerConfig::default()
+        };
+        assert_eq!(
+            argv(&tarpaulin.command(Path::new("/t"), None, false)),
+            ["tarpaulin", "--target-dir", "/t", "--workspace"]
+        );
+        assert_eq!(
+            argv(&tarpaulin.command(Path::new("/t"), Some("cli"), true)),
+            [
+                "tarpaulin",
+                "--target-dir",
+                "/t",
+                "--message-format",
+                "json",
+                "--workspace",
+                "--",
+                "cli"
+            ]
+        );
+
+        let llvm_cov = RunnerConfig {
+            backend: Backend::LlvmCov,
+            workspace: true,
+            ..RunnerConfig::default()
+        };
+        assert_eq!(
+            argv(&llvm_cov.command(Path::new("/t"), None, false)),
+            [
+                "llvm-cov",
+                "--json",
+                "--output-path",
+                "/t/llvm-cov.json",
+                "--target-dir",
+                "/t",
+                "--workspace"
+            ]
+        );
+        assert_eq!(
+            argv(&llvm_cov.command(Path::new("/t"), Some("cli"), true)),
+            [
+                "llvm-cov",
+                "--json",
+                "--output-path",
+                "/t/llvm-cov.json",
+                "--target-dir",
+                "/t",
+                "--message-format",
+                "json",
+                "--workspace",
+                "--",
+                "cli"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filter() {
+        let args = |config: &RunnerConfig| {
+            argv(&config.command(Path::new("target"), Some("cli::test"), false))
+        };
+
+        let plain = args(&RunnerConfig::default());
+        assert_eq!(plain[plain.len() - 2..], ["--", "cli::test"]);
+
+        let separated = args(&RunnerConfig {
+            args: vec!["--".to_string(), "--test-threads=1".to_string()],
+            ..RunnerConfig::default()
+        });
+        assert_eq!(
+            separated[separated.len() - 3..],
+            ["--", "--test-threads=1", "cli::test"]
+        );
+    }
+
+    #[test]
+    fn test_args() {
+        let config: Config = toml::from_str(
+            r#"
+[runner]
+features = ["serde", "tokio"]
+workspace = true
+engine = "llvm"
+skip-clean = true
+timeout = 120
+args = ["--exclude-files", "src/main.rs"]
+env = { RUSTFLAGS = "-C debuginfo=1" }
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.runner.args(),
+            vec![
+                "--features",
+                "serde,tokio",
+                "--workspace",
+                "--engine",
+                "llvm",
+                "--skip-clean",
+                "--timeout",
+                "120",
+                "--exclude-files",
+                "src/main.rs",
+            ]
+        );
+        assert_eq!(config.runner.env["RUSTFLAGS"], "-C debuginfo=1");
+    }
+
+    #[test]
+    fn test_llvm_cov_args() {
+        let config: Config = toml::from_str(
+            r#"
+[runner]
+backend = "llvm-cov"
+packages = ["demo"]
+skip-clean = true
+timeout = 120
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.runner.args(),
+            vec!["--package", "demo", "--no-clean"]
+        );
+
+        let source = config.source("demo".to_string(), Path::new("/t"), Path::new("/r"));
+        assert!(matches!(
+            source,
+            Source::File { path, format: Format::LlvmCov, .. } if path == Path::new("/t/llvm-cov.json")
+        ));
+    }
+
+    #[test]
+    fn test_coverage() {
+        let config: Config = toml::from_str(
+            r#"
+[coverage]
+path = "target/lcov.info"
+format = "llvm-cov"
+offline = true
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.coverage.path.as_deref(),
+            Some(Path::new("target/lcov.info"))
+        );
+        assert_eq!(config.coverage.format, Format::LlvmCov);
+        assert!(config.coverage.offline);
+    }
+
+    #[test]
+    fn test_hints() {
+        let config: Config = toml::from_str(
+            r#"
+[hints]
+show = "cold"
+cold = 2
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.hints.hot, 1000);
+        assert!(config.hints.shows(0));
+        assert!(config.hints.shows(2));
+        assert!(!config.hints.shows(3));
+
+        let hot = HintsConfig {
+            show: Hints::Hot,
+            ..HintsConfig::default()
+        };
+        assert!(!hot.shows(999));
+        assert!(hot.shows(1523));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut base = serde_json::json!({
+            "runner": { "features": ["a"], "lib": true },
+        });
+
+        merge(
+            &mut base,
+            serde_json::json!({ "runner": { "features": ["b"], "tests": true } }),
+        );
+
+        let config: Config = serde_json::from_value(base).unwrap();
+        assert_eq!(config.runner.features, vec!["b"]);
+        assert!(config.runner.lib);
+        assert!(config.runner.tests);
+    }
+}
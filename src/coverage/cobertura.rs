@@ -0,0 +1,239 @@
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    path::{Path, PathBuf},
+};
+
+use crate::Error;
+
+use super::{Coverage, Stats, Trace};
+
+/// reads a cobertura xml report
+///
+/// only the handful of elements carrying coverage are looked at, so this is a
+/// tag scanner rather than a full xml parser
+pub fn parse(content: &str) -> Result<Coverage, Error> {
+    let mut coverage = Coverage::default();
+    let mut sources = Vec::<PathBuf>::new();
+    let mut file: Option<(PathBuf, BTreeMap<u32, Vec<Trace>>)> = None;
+    let mut method: Option<String> = None;
+
+    let mut rest = content;
+    while let Some(start) = rest.find('<') {
+        let text = &rest[..start];
+        rest = &rest[start..];
+
+        if rest.starts_with("<!--") {
+            let end = rest
+                .find("-->")
+                .ok_or_else(|| invalid("unterminated comment"))?;
+            rest = &rest[end + 3..];
+            continue;
+        }
+
+        let end = rest.find('>').ok_or_else(|| invalid("unterminated tag"))?;
+        let tag = Tag::parse(&rest[1..end]);
+        rest = &rest[end + 1..];
+
+        let Some(tag) = tag else {
+            continue;
+        };
+
+        match (tag.name, tag.closing) {
+            ("source", true) => sources.push(PathBuf::from(unescape(text.trim()))),
+
+            ("class", false) => {
+                let filename = tag
+                    .attr("filename")
+                    .ok_or_else(|| invalid("class without filename"))?;
+                file = Some((PathBuf::from(filename), BTreeMap::new()));
+
+                if tag.empty {
+                    file = None;
+                }
+            }
+
+            ("class", true) => {
+                if let Some((path, lines)) = file.take() {
+                    let path = resolve(&sources, &path);
+                    let traces = coverage.traces.entry(path).or_default();
+                    traces.extend(lines.into_values().flatten());
+                }
+            }
+
+            ("method", false) if !tag.empty => method = tag.attr("name"),
+            ("method", true) => method = None,
+
+            ("line", false) => {
+                let Some((_, lines)) = file.as_mut() else {
+                    continue;
+                };
+
+                let number = tag
+                    .attr("number")
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(|| invalid("line without number"))?;
+                let hits = tag
+                    .attr("hits")
+                    .and_then(|n| n.parse().ok())
+                    .ok_or_else(|| invalid("line without hits"))?;
+
+                // lines show up both under their method and their class
+                match lines.entry(number) {
+                    Entry::Occupied(mut entry) => {
+                        for trace in entry.get_mut() {
+                            if trace.fn_name.is_none() {
+                                trace.fn_name.clone_from(&method);
+                            }
+                        }
+                    }
+
+                    Entry::Vacant(entry) => {
+                        let mut line = Trace::new(number, Stats::Line(hits));
+                        line.fn_name.clone_from(&method);
+
+                        let mut traces = vec![line];
+                        if tag.attr("branch").as_deref() == Some("true") {
+                            if let Some(stats) =
+                                tag.attr("condition-coverage").and_then(|c| conditions(&c))
+                            {
+                                traces.push(Trace::new(number, stats));
+                            }
+                        }
+
+                        entry.insert(traces);
+                    }
+                }
+            }
+
+            _ => (),
+        }
+    }
+
+    Ok(coverage)
+}
+
+/// a start, end or empty element tag
+struct Tag<'a> {
+    name: &'a str,
+    closing: bool,
+    empty: bool,
+    attrs: &'a str,
+}
+
+impl<'a> Tag<'a> {
+    /// `None` for declarations and processing instructions
+    fn parse(inner: &'a str) -> Option<Tag<'a>> {
+        if inner.starts_with('?') || inner.starts_with('!') {
+            return None;
+        }
+
+        let (closing, inner) = match inner.strip_prefix('/') {
+            Some(inner) => (true, inner),
+            None => (false, inner),
+        };
+
+        let (empty, inner) = match inner.strip_suffix('/') {
+            Some(inner) => (true, inner),
+            None => (false, inner),
+        };
+
+        let (name, attrs) = inner.split_once(char::is_whitespace).unwrap_or((inner, ""));
+
+        Some(Tag {
+            name,
+            closing,
+            empty,
+            attrs,
+        })
+    }
+
+    fn attr(&self, key: &str) -> Option<String> {
+        let mut rest = self.attrs;
+
+        while let Some((name, after)) = rest.split_once('=') {
+            let after = after.trim_start();
+            let quote = after.chars().next()?;
+            let after = &after[quote.len_utf8()..];
+            let end = after.find(quote)?;
+
+            if name.trim() == key {
+                return Some(unescape(&after[..end]));
+            }
+
+            rest = &after[end + 1..];
+        }
+
+        None
+    }
+}
+
+/// `50% (1/2)` into how many of the outcomes were taken
+fn conditions(coverage: &str) -> Option<Stats> {
+    let (_, counts) = coverage.split_once('(')?;
+    let (taken, total) = counts.trim_end_matches(')').split_once('/')?;
+
+    Some(Stats::Outcomes {
+        taken: taken.trim().parse().ok()?,
+        total: total.trim().parse().ok()?,
+    })
+}
+
+fn resolve(sources: &[PathBuf], path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+
+    match sources.first() {
+        Some(source) => source.join(path),
+        None => path.to_path_buf(),
+    }
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::Format("cobertura", reason.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let coverage = parse(include_str!("../../tests/fixtures/cobertura.xml")).unwrap();
+
+        let lib = &coverage.traces[Path::new("/work/demo/src/lib.rs")];
+        let lines = lib
+            .iter()
+            .map(|trace| (trace.line, trace.stats.covered(), trace.stats.untaken()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            vec![
+                (1, true, None),
+                (2, true, None),
+                (6, true, None),
+                (6, true, Some("only 1 of 2 branches taken".to_string())),
+                (9, false, None),
+            ]
+        );
+        assert_eq!(lib[0].fn_name.as_deref(), Some("add"));
+        assert_eq!(lib[1].fn_name, None);
+
+        let escaped = &coverage.traces[Path::new("/work/demo/src/a&b.rs")];
+        assert_eq!(escaped.len(), 1);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(parse("<coverage><class name=\"x\"><lines><line hits=\"1\"/>").is_err());
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+};
+
+use crate::Error;
+
+use super::{Coverage, Stats, Trace};
+
+/// reads an lcov tracefile, `DA` records become lines and `BRDA` records branches
+pub fn parse(content: &str) -> Result<Coverage, Error> {
+    let mut coverage = Coverage::default();
+    let mut file: Option<Record> = None;
+
+    for (n, line) in content.lines().enumerate() {
+        let line = line.trim();
+        let (key, value) = line.split_once(':').unwrap_or((line, ""));
+        let invalid = || Error::Format("lcov", format!("line {}: {line}", n + 1));
+
+        if key == "SF" {
+            file = Some(Record::new(value));
+            continue;
+        }
+
+        if key == "end_of_record" {
+            if let Some(record) = file.take() {
+                let (path, traces) = record.finish();
+                coverage.traces.entry(path).or_default().extend(traces);
+            }
+            continue;
+        }
+
+        let Some(record) = file.as_mut() else {
+            continue;
+        };
+
+        match key {
+            "DA" => {
+                let mut fields = value.split(',');
+                let line = fields
+                    .next()
+                    .and_then(|f| f.parse().ok())
+                    .ok_or_else(invalid)?;
+                let hits: usize = fields
+                    .next()
+                    .and_then(|f| f.parse().ok())
+                    .ok_or_else(invalid)?;
+
+                *record.lines.entry(line).or_default() += hits;
+            }
+
+            "BRDA" => {
+                let fields = value.split(',').collect::<Vec<_>>();
+                let [line, block, _, taken] = fields[..] else {
+                    return Err(invalid());
+                };
+
+                let line = line.parse().map_err(|_| invalid())?;
+                let block = block.parse().map_err(|_| invalid())?;
+                let taken = taken != "-" && taken != "0";
+
+                record
+                    .branches
+                    .entry((line, block))
+                    .or_default()
+                    .push(taken);
+            }
+
+            "FN" => {
+                if let Some((line, name)) = value.split_once(',') {
+                    let line = line.parse().map_err(|_| invalid())?;
+                    record.functions.insert(line, name.to_string());
+                }
+            }
+
+            _ => (),
+        }
+    }
+
+    Ok(coverage)
+}
+
+/// the accumulated records of one `SF` section
+struct Record {
+    path: PathBuf,
+    lines: BTreeMap<u32, usize>,
+    branches: BTreeMap<(u32, u32), Vec<bool>>,
+    functions: HashMap<u32, String>,
+}
+
+impl Record {
+    fn new(path: &str) -> Self {
+        Record {
+            path: PathBuf::from(path),
+            lines: BTreeMap::new(),
+            branches: BTreeMap::new(),
+            functions: HashMap::new(),
+        }
+    }
+
+    fn finish(mut self) -> (PathBuf, Vec<Trace>) {
+        let mut traces = Vec::with_capacity(self.lines.len() + self.branches.len());
+
+        for (line, hits) in self.lines {
+            let mut trace = Trace::new(line, Stats::Line(hits));
+            trace.fn_name = self.functions.remove(&line);
+            traces.push(trace);
+        }
+
+        for ((line, _), taken) in self.branches {
+            traces.push(Trace::new(line, Stats::outcomes(&taken)));
+        }
+
+        traces.sort_by_key(|trace| trace.line);
+
+        (self.path, traces)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let coverage = parse(include_str!("../../tests/fixtures/lcov.info")).unwrap();
+
+        let lib = &coverage.traces[Path::new("src/lib.rs")];
+        let lines = lib
+            .iter()
+            .map(|trace| (trace.line, trace.stats.covered(), trace.stats.untaken()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            vec![
+                (1, true, None),
+                (2, true, None),
+                (5, true, None),
+                (6, true, None),
+                (6, true, Some("only 1 of 2 branches taken".to_string())),
+                (7, true, None),
+                (9, false, None),
+                (9, false, None),
+            ]
+        );
+        assert_eq!(lib[0].fn_name.as_deref(), Some("add"));
+        assert_eq!(lib[2].fn_name.as_deref(), Some("pick"));
+
+        let main = &coverage.traces[Path::new("/work/demo/src/main.rs")];
+        assert_eq!(main.len(), 2);
+        assert!(main.iter().all(|trace| !trace.stats.covered()));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(parse("SF:src/lib.rs\nDA:one,1\nend_of_record\n").is_err());
+    }
+}
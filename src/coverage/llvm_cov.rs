@@ -0,0 +1,272 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::Error;
+
+use super::{Coverage, LogicState, Span, Stats, Trace};
+
+/// `llvm-cov export` json, as written by `cargo llvm-cov --json`
+#[derive(Deserialize)]
+struct Export {
+    data: Vec<Data>,
+}
+
+#[derive(Deserialize)]
+struct Data {
+    files: Vec<File>,
+}
+
+#[derive(Deserialize)]
+struct File {
+    filename: PathBuf,
+    #[serde(default)]
+    segments: Vec<Vec<Value>>,
+    #[serde(default)]
+    branches: Vec<Vec<Value>>,
+}
+
+/// a point where the execution count changes
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Segment {
+    line: u32,
+    col: u32,
+    count: usize,
+    has_count: bool,
+    region_entry: bool,
+    gap: bool,
+}
+
+pub fn parse(content: &str) -> Result<Coverage, Error> {
+    let export: Export = serde_json::from_str(content)?;
+    let mut coverage = Coverage::default();
+
+    for data in export.data {
+        for file in data.files {
+            let segments = file
+                .segments
+                .iter()
+                .map(|segment| Segment::parse(segment))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut traces = lines(&segments);
+            uncovered(&segments, &mut traces);
+
+            for branch in &file.branches {
+                let line = branch.first().and_then(Value::as_u64);
+                let true_count = branch.get(4).and_then(Value::as_u64);
+                let false_count = branch.get(5).and_then(Value::as_u64);
+
+                let (Some(line), Some(true_count), Some(false_count)) =
+                    (line, true_count, false_count)
+                else {
+                    return Err(invalid("malformed branch"));
+                };
+
+                traces.push(Trace::new(
+                    line as u32,
+                    Stats::Branch(LogicState {
+                        been_true: true_count > 0,
+                        been_false: false_count > 0,
+                    }),
+                ));
+            }
+
+            traces.sort_by_key(|trace| trace.line);
+
+            coverage
+                .traces
+                .entry(file.filename)
+                .or_default()
+                .extend(traces);
+        }
+    }
+
+    Ok(coverage)
+}
+
+/// line counts from segments, following llvm-cov's own `LineCoverageStats`
+///
+/// a line takes the count of the region wrapping into it, or of the busiest region
+/// starting on it. Lines starting a skipped region or covered by no region are unmapped.
+fn lines(segments: &[Segment]) -> Vec<Trace> {
+    let mut traces = Vec::new();
+
+    let (Some(first), Some(last)) = (segments.first(), segments.last()) else {
+        return traces;
+    };
+
+    let mut wrapped: Option<&Segment> = None;
+    let mut i = 0;
+
+    for line in first.line..=last.line {
+        let start = i;
+        while i < segments.len() && segments[i].line == line {
+            i += 1;
+        }
+        let on_line = &segments[start..i];
+
+        let starts = on_line
+            .iter()
+            .filter(|s| !s.gap && s.has_count && s.region_entry)
+            .collect::<Vec<_>>();
+
+        let skipped = on_line
+            .first()
+            .is_some_and(|s| !s.has_count && s.region_entry);
+
+        let mapped = !skipped && (wrapped.is_some_and(|w| w.has_count) || !starts.is_empty());
+
+        if mapped {
+            let wrapped_count = wrapped.map(|w| w.count).unwrap_or_default();
+            let count = starts
+                .iter()
+                .map(|s| s.count)
+                .fold(wrapped_count, usize::max);
+
+            traces.push(Trace::new(line, Stats::Line(count)));
+        }
+
+        if let Some(last) = on_line.last() {
+            wrapped = Some(last);
+        }
+    }
+
+    traces
+}
+
+/// attaches the columns of never executed regions to the lines they touch
+///
+/// this is what singles out an `else` arm or a `?` return on a line that did run
+fn uncovered(segments: &[Segment], traces: &mut [Trace]) {
+    for pair in segments.windows(2) {
+        let [from, to] = pair else { continue };
+
+        if from.gap || !from.has_count || from.count > 0 {
+            continue;
+        }
+
+        for line in from.line..=to.line {
+            let span = Span {
+                start: if line == from.line { from.col } else { 1 },
+                end: (line == to.line).then_some(to.col),
+            };
+
+            if span.end.is_some_and(|end| end <= span.start) {
+                continue;
+            }
+
+            if let Some(trace) = traces.iter_mut().find(|trace| trace.line == line) {
+                trace.uncovered.push(span);
+            }
+        }
+    }
+}
+
+impl Segment {
+    fn parse(fields: &[Value]) -> Result<Segment, Error> {
+        let number = |i| fields.get(i).and_then(Value::as_u64);
+        let flag = |i| fields.get(i).and_then(Value::as_bool);
+
+        let (Some(line), Some(col), Some(count), Some(has_count), Some(region_entry)) =
+            (number(0), number(1), number(2), flag(3), flag(4))
+        else {
+            return Err(invalid("malformed segment"));
+        };
+
+        Ok(Segment {
+            line: line as u32,
+            col: col as u32,
+            count: count as usize,
+            has_count,
+            region_entry,
+            // older exports predate gap regions
+            gap: flag(5).unwrap_or(false),
+        })
+    }
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::Format("llvm-cov", reason.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let coverage = parse(include_str!("../../tests/fixtures/llvm-cov.json")).unwrap();
+
+        let lib = &coverage.traces[Path::new("/work/demo/src/lib.rs")];
+        let lines = lib
+            .iter()
+            .map(|trace| (trace.line, trace.stats.clone()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            lines,
+            vec![
+                (1, Stats::Line(3)),
+                (2, Stats::Line(3)),
+                (3, Stats::Line(3)),
+                (5, Stats::Line(1)),
+                (6, Stats::Line(1)),
+                (
+                    6,
+                    Stats::Branch(LogicState {
+                        been_true: true,
+                        been_false: false
+                    })
+                ),
+                (7, Stats::Line(1)),
+                (8, Stats::Line(1)),
+                (9, Stats::Line(0)),
+                (10, Stats::Line(0)),
+                (11, Stats::Line(1)),
+            ]
+        );
+
+        let uncovered = lib
+            .iter()
+            .filter(|trace| !trace.uncovered.is_empty())
+            .map(|trace| (trace.line, trace.uncovered.clone()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            uncovered,
+            vec![
+                (
+                    8,
+                    vec![Span {
+                        start: 12,
+                        end: None
+                    }]
+                ),
+                (
+                    9,
+                    vec![Span {
+                        start: 1,
+                        end: None
+                    }]
+                ),
+                (
+                    10,
+                    vec![Span {
+                        start: 1,
+                        end: Some(6)
+                    }]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let content = r#"{"data":[{"files":[{"filename":"a.rs","segments":[[1,true]]}]}]}"#;
+        assert!(parse(content).is_err());
+    }
+}
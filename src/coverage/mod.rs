@@ -0,0 +1,356 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::Error;
+
+mod cobertura;
+mod lcov;
+mod llvm_cov;
+
+/// coverage in tarpaulin's model, which every other format is converted into
+#[derive(Deserialize, Serialize, Default, Debug)]
+pub struct Coverage {
+    pub traces: HashMap<PathBuf, Vec<Trace>>,
+}
+
+// mirrors tarpaulin's trace format, not every field is consumed
+#[allow(dead_code)]
+#[derive(Deserialize, Serialize, Clone, Hash, Debug, PartialEq)]
+pub struct Trace {
+    pub line: u32,
+    #[serde(default)]
+    pub address: Vec<usize>,
+    #[serde(default)]
+    pub length: usize,
+    pub stats: Stats,
+    pub fn_name: Option<String>,
+    /// parts of the line no test reached, only known for region based formats
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub uncovered: Vec<Span>,
+}
+
+/// columns on a line, one based like llvm reports them
+#[derive(Deserialize, Serialize, Clone, Copy, Hash, Debug, PartialEq)]
+pub struct Span {
+    pub start: u32,
+    /// exclusive, `None` runs to the end of the line
+    pub end: Option<u32>,
+}
+
+/// tarpaulin's `CoverageStat`
+#[derive(Deserialize, Serialize, Clone, Hash, Debug, PartialEq)]
+pub enum Stats {
+    Line(usize),
+    Branch(LogicState),
+    Condition(Vec<LogicState>),
+    /// branch outcomes of a format that doesn't say which side each one is
+    Outcomes {
+        taken: usize,
+        total: usize,
+    },
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Hash, Debug, PartialEq)]
+pub struct LogicState {
+    pub been_true: bool,
+    pub been_false: bool,
+}
+
+impl Trace {
+    pub fn new(line: u32, stats: Stats) -> Self {
+        Trace {
+            line,
+            address: Vec::new(),
+            length: 0,
+            stats,
+            fn_name: None,
+            uncovered: Vec::new(),
+        }
+    }
+}
+
+impl Stats {
+    /// the branch outcomes a format lists, without knowing which side each one is
+    pub fn outcomes(taken: &[bool]) -> Self {
+        Stats::Outcomes {
+            taken: taken.iter().filter(|taken| **taken).count(),
+            total: taken.len(),
+        }
+    }
+
+    /// whether the trace was executed at all
+    pub fn covered(&self) -> bool {
+        match self {
+            Stats::Line(hits) => *hits > 0,
+            Stats::Branch(state) => state.taken(),
+            Stats::Condition(states) => states.iter().any(LogicState::taken),
+            Stats::Outcomes { taken, .. } => *taken > 0,
+        }
+    }
+
+    /// describes the outcomes of an executed branch or condition that never happened
+    pub fn untaken(&self) -> Option<String> {
+        match self {
+            Stats::Line(_) => None,
+            Stats::Branch(state) => state.untaken().map(str::to_string),
+            Stats::Condition(states) => {
+                if !self.covered() {
+                    return None;
+                }
+
+                let partial = states.iter().filter(|s| s.untaken().is_some()).count();
+                if partial == 0 {
+                    return None;
+                }
+
+                Some(format!(
+                    "{partial} of {} conditions not evaluated both ways",
+                    states.len()
+                ))
+            }
+            Stats::Outcomes { taken, total } => (*taken > 0 && taken < total)
+                .then(|| format!("only {taken} of {total} branches taken")),
+        }
+    }
+}
+
+impl LogicState {
+    fn taken(&self) -> bool {
+        self.been_true || self.been_false
+    }
+
+    fn untaken(&self) -> Option<&'static str> {
+        match (self.been_true, self.been_false) {
+            (true, false) => Some("condition was never false"),
+            (false, true) => Some("condition was never true"),
+            _ => None,
+        }
+    }
+}
+
+/// the coverage report formats that can be read
+#[derive(Deserialize, clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    /// decided from the file name and contents
+    #[default]
+    Auto,
+    Tarpaulin,
+    Lcov,
+    Cobertura,
+    LlvmCov,
+}
+
+/// where the coverage a server shows comes from
+#[derive(Debug, Clone)]
+pub enum Source {
+    /// the report tarpaulin leaves in its target directory
+    Tarpaulin { package: String, target: PathBuf },
+
+    /// a report file in any supported format, relative paths in it are taken from `root`
+    File {
+        path: PathBuf,
+        format: Format,
+        root: PathBuf,
+    },
+}
+
+impl Source {
+    /// the report file a load reads
+    pub fn path(&self) -> PathBuf {
+        match self {
+            Source::Tarpaulin { package, target } => Coverage::path(package, target),
+            Source::File { path, .. } => path.clone(),
+        }
+    }
+
+    pub fn load(&self) -> Result<Coverage, Error> {
+        match self {
+            Source::Tarpaulin { package, target } => Coverage::load(package, target),
+            Source::File { path, format, root } => {
+                debug!(path = %path.display(), ?format, "loading coverage file");
+
+                let content = std::fs::read_to_string(path)?;
+                let coverage = Coverage::parse(&content, format.detect(path, &content))?;
+
+                Ok(coverage.rooted(root))
+            }
+        }
+    }
+}
+
+impl Format {
+    /// resolves [`Format::Auto`] by extension, falling back to sniffing the content
+    pub fn detect(self, path: &Path, content: &str) -> Format {
+        if self != Format::Auto {
+            return self;
+        }
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("info" | "lcov") => return Format::Lcov,
+            Some("xml") => return Format::Cobertura,
+            _ => (),
+        }
+
+        let content = content.trim_start();
+        if content.starts_with('<') {
+            Format::Cobertura
+        } else if content.starts_with('{') {
+            if content.contains("\"llvm.coverage.json.export\"") {
+                Format::LlvmCov
+            } else {
+                Format::Tarpaulin
+            }
+        } else {
+            Format::Lcov
+        }
+    }
+}
+
+impl Coverage {
+    pub fn load(package: &str, target: &Path) -> Result<Self, Error> {
+        let path = Coverage::path(package, target);
+
+        debug!(path = %path.display(), "looking for coverage file");
+
+        let content = std::fs::read_to_string(path)?;
+
+        Coverage::parse(&content, Format::Tarpaulin)
+    }
+
+    fn path(package: &str, target: &Path) -> PathBuf {
+        let mut path = target.to_path_buf();
+        path.push("tarpaulin");
+        path.push(format!("{package}-coverage.json"));
+        path
+    }
+
+    pub fn parse(content: &str, format: Format) -> Result<Self, Error> {
+        match format {
+            Format::Auto => Coverage::parse(content, format.detect(Path::new(""), content)),
+            Format::Tarpaulin => Ok(serde_json::from_str(content)?),
+            Format::Lcov => lcov::parse(content),
+            Format::Cobertura => cobertura::parse(content),
+            Format::LlvmCov => llvm_cov::parse(content),
+        }
+    }
+
+    /// anchors relative file paths at `root`
+    fn rooted(self, root: &Path) -> Self {
+        let traces = self
+            .traces
+            .into_iter()
+            .map(|(path, traces)| (root.join(path), traces))
+            .collect();
+
+        Coverage { traces }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        const CONTENT: &str = r#"{"traces": {"src/lib.rs": [
+            {"line": 1, "address": [], "length": 1, "stats": {"Line": 3}, "fn_name": null},
+            {"line": 2, "address": [], "length": 1, "stats": {"Branch": {"been_true": true, "been_false": false}}, "fn_name": null},
+            {"line": 3, "address": [], "length": 1, "stats": {"Condition": [{"been_true": true, "been_false": true}, {"been_true": false, "been_false": true}]}, "fn_name": null},
+            {"line": 4, "address": [], "length": 1, "stats": {"Branch": {"been_true": false, "been_false": false}}, "fn_name": null}
+        ]}}"#;
+
+        let coverage: Coverage = serde_json::from_str(CONTENT).unwrap();
+        let traces = &coverage.traces[&PathBuf::from("src/lib.rs")];
+
+        assert_eq!(traces[0].stats, Stats::Line(3));
+        assert_eq!(traces[0].stats.untaken(), None);
+
+        assert!(traces[1].stats.covered());
+        assert_eq!(
+            traces[1].stats.untaken().as_deref(),
+            Some("condition was never false")
+        );
+
+        assert!(traces[2].stats.covered());
+        assert_eq!(
+            traces[2].stats.untaken().as_deref(),
+            Some("1 of 2 conditions not evaluated both ways")
+        );
+
+        assert!(!traces[3].stats.covered());
+        assert_eq!(traces[3].stats.untaken(), None);
+    }
+
+    #[test]
+    fn test_outcomes() {
+        // the order of the outcomes says nothing about which side of a branch they are
+        assert_eq!(
+            Stats::outcomes(&[true, false]),
+            Stats::outcomes(&[false, true])
+        );
+
+        let stats = Stats::outcomes(&[true, false]);
+        assert!(stats.covered());
+        assert_eq!(
+            stats.untaken().as_deref(),
+            Some("only 1 of 2 branches taken")
+        );
+
+        let stats = Stats::outcomes(&[true, false, true]);
+        assert!(stats.covered());
+        assert_eq!(
+            stats.untaken().as_deref(),
+            Some("only 2 of 3 branches taken")
+        );
+
+        assert_eq!(Stats::outcomes(&[true, true, true]).untaken(), None);
+        assert!(!Stats::outcomes(&[false, false, false]).covered());
+        assert_eq!(Stats::outcomes(&[false, false]).untaken(), None);
+    }
+
+    #[test]
+    fn test_detect() {
+        const TARPAULIN: &str = include_str!("../../tests/fixtures/tarpaulin.json");
+        const LCOV: &str = include_str!("../../tests/fixtures/lcov.info");
+        const COBERTURA: &str = include_str!("../../tests/fixtures/cobertura.xml");
+        const LLVM_COV: &str = include_str!("../../tests/fixtures/llvm-cov.json");
+
+        let unnamed = Path::new("coverage");
+        assert_eq!(Format::Auto.detect(unnamed, TARPAULIN), Format::Tarpaulin);
+        assert_eq!(Format::Auto.detect(unnamed, LCOV), Format::Lcov);
+        assert_eq!(Format::Auto.detect(unnamed, COBERTURA), Format::Cobertura);
+        assert_eq!(Format::Auto.detect(unnamed, LLVM_COV), Format::LlvmCov);
+
+        assert_eq!(
+            Format::Auto.detect(Path::new("lcov.info"), ""),
+            Format::Lcov
+        );
+        assert_eq!(
+            Format::Lcov.detect(Path::new("coverage.xml"), COBERTURA),
+            Format::Lcov
+        );
+    }
+
+    #[test]
+    fn test_tarpaulin() {
+        let coverage = Coverage::parse(
+            include_str!("../../tests/fixtures/tarpaulin.json"),
+            Format::Tarpaulin,
+        )
+        .unwrap();
+
+        let traces = &coverage.traces[Path::new("/work/demo/src/lib.rs")];
+        let lines = traces
+            .iter()
+            .map(|trace| (trace.line, trace.stats.covered()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(lines, vec![(1, true), (2, true), (3, false), (6, false)]);
+    }
+}
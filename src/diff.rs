@@ -0,0 +1,214 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use eyre::Context;
+
+/// lines added or modified in the working tree relative to a git base
+#[derive(Debug, Default, PartialEq)]
+pub struct Changes {
+    files: HashMap<PathBuf, Changed>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Changed {
+    /// a file git does not track yet
+    All,
+    Lines(BTreeSet<u32>),
+}
+
+impl Changes {
+    /// asks git in `root` what changed since the branch left `base`, paths come back joined onto `root`
+    ///
+    /// diffing against the merge base keeps changes that only landed on `base` out
+    pub fn load(root: &Path, base: &str) -> eyre::Result<Self> {
+        let fork = git(root, &["merge-base", base, "HEAD"])?;
+        let diff = git(
+            root,
+            &[
+                "diff",
+                "--relative",
+                "--no-color",
+                "--no-ext-diff",
+                // the parser relies on these, whatever diff.noprefix or diff.mnemonicPrefix say
+                "--src-prefix=a/",
+                "--dst-prefix=b/",
+                "-U0",
+                fork.trim(),
+                "--",
+            ],
+        )?;
+        let untracked = git(root, &["ls-files", "--others", "--exclude-standard"])?;
+
+        let mut changes = Changes::parse(&diff);
+        for path in untracked.lines().filter(|path| !path.is_empty()) {
+            changes.files.insert(PathBuf::from(path), Changed::All);
+        }
+
+        changes.files = changes
+            .files
+            .into_iter()
+            .map(|(path, changed)| (root.join(path), changed))
+            .collect();
+
+        Ok(changes)
+    }
+
+    /// reads the new side of a zero context unified diff
+    fn parse(diff: &str) -> Self {
+        let mut files = HashMap::new();
+        let mut current: Option<&mut BTreeSet<u32>> = None;
+
+        for line in diff.lines() {
+            if let Some(path) = line.strip_prefix("+++ ") {
+                current = match path.strip_prefix("b/") {
+                    Some(path) => match files
+                        .entry(PathBuf::from(path))
+                        .or_insert_with(|| Changed::Lines(BTreeSet::new()))
+                    {
+                        Changed::Lines(lines) => Some(lines),
+                        Changed::All => None,
+                    },
+                    // deleted files have nothing left to cover
+                    None => None,
+                };
+                continue;
+            }
+
+            let Some(hunk) = line.strip_prefix("@@ ") else {
+                continue;
+            };
+
+            let (Some(lines), Some((start, count))) = (current.as_mut(), new_range(hunk)) else {
+                continue;
+            };
+
+            lines.extend(start..start + count);
+        }
+
+        Changes { files }
+    }
+
+    pub fn contains(&self, path: &Path, line: u32) -> bool {
+        match self.files.get(path) {
+            Some(Changed::All) => true,
+            Some(Changed::Lines(lines)) => lines.contains(&line),
+            None => false,
+        }
+    }
+}
+
+/// the `+start,count` part of a hunk header, the count defaults to one
+fn new_range(hunk: &str) -> Option<(u32, u32)> {
+    let range = hunk
+        .split_whitespace()
+        .find_map(|part| part.strip_prefix('+'))?;
+
+    match range.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+fn git(root: &Path, args: &[&str]) -> eyre::Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()
+        .wrap_err("failed to run git")?;
+
+    eyre::ensure!(
+        output.status.success(),
+        "git {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const DIFF: &str = r#"diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -3,0 +4,2 @@ fn a() {
++    b();
++    c();
+@@ -10 +12 @@ fn d() {
+-    old();
++    new();
+@@ -20,2 +22,0 @@ fn e() {
+-    gone();
+-    gone();
+diff --git a/src/old.rs b/src/old.rs
+deleted file mode 100644
+--- a/src/old.rs
++++ /dev/null
+@@ -1 +0,0 @@
+-fn old() {}
+"#;
+
+    #[test]
+    fn test_parse() {
+        let changes = Changes::parse(DIFF);
+        let lib = Path::new("src/lib.rs");
+
+        let changed = (1..30)
+            .filter(|line| changes.contains(lib, *line))
+            .collect::<Vec<_>>();
+
+        assert_eq!(changed, vec![4, 5, 12]);
+        assert!(!changes.contains(Path::new("src/old.rs"), 1));
+    }
+
+    #[test]
+    fn test_load() {
+        let root = tempdir::TempDir::new("tarballin-diff").unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(root.path())
+                .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {args:?}");
+        };
+
+        git(&["init", "-q"]);
+        git(&["config", "diff.noprefix", "true"]);
+        std::fs::write(root.path().join("lib.rs"), "fn a() {}\nfn b() {}\n").unwrap();
+        std::fs::write(root.path().join("up.rs"), "fn u() {}\n").unwrap();
+        git(&["add", "lib.rs", "up.rs"]);
+        git(&["commit", "-q", "-m", "base"]);
+
+        // the base moves on without this branch
+        git(&["branch", "upstream"]);
+        git(&["checkout", "-q", "upstream"]);
+        std::fs::write(root.path().join("up.rs"), "fn u() {}\nfn v() {}\n").unwrap();
+        git(&["commit", "-q", "-am", "upstream"]);
+        git(&["checkout", "-q", "-"]);
+
+        std::fs::write(
+            root.path().join("lib.rs"),
+            "fn a() {}\nfn c() {}\nfn b() {}\n",
+        )
+        .unwrap();
+        std::fs::write(root.path().join("new.rs"), "fn n() {}\n").unwrap();
+
+        let changes = Changes::load(root.path(), "upstream").unwrap();
+
+        assert!(changes.contains(&root.path().join("lib.rs"), 2));
+        assert!(!changes.contains(&root.path().join("lib.rs"), 3));
+        assert!(changes.contains(&root.path().join("new.rs"), 1));
+        assert!(!changes.contains(&root.path().join("up.rs"), 2));
+    }
+}
@@ -0,0 +1,257 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use tree_sitter::{Node, Point, Tree};
+
+use crate::{
+    line_slice::LineSlice,
+    summary::Line,
+    syntax::{self, Function},
+};
+
+/// how uncovered lines are gathered into diagnostics
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Grouping {
+    /// a diagnostic per uncovered line
+    Line,
+    /// consecutive uncovered lines together, stretched over the statements they start
+    #[default]
+    Block,
+    /// like block, but a function that never ran at all is one diagnostic
+    Function,
+}
+
+/// a stretch of uncovered code, zero based like the editor wants it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Uncovered {
+    pub start: Point,
+    pub end: Point,
+    /// the function when all of it is uncovered
+    pub function: Option<String>,
+}
+
+/// the uncovered parts of a file
+pub fn uncovered(
+    content: &[u8],
+    lines: &BTreeMap<u32, Line>,
+    grouping: Grouping,
+) -> Vec<Uncovered> {
+    let slices = LineSlice::build(content);
+    let tree = match grouping {
+        Grouping::Line => None,
+        _ => syntax::parse(content).ok(),
+    };
+
+    let Some(tree) = tree else {
+        return lines
+            .iter()
+            .filter(|(_, line)| !line.covered)
+            .filter_map(|(number, _)| line_range(&slices, *number, *number))
+            .collect();
+    };
+
+    let mut functions = Vec::new();
+    if grouping == Grouping::Function {
+        for function in syntax::functions(&tree, content) {
+            let first = function.start.row as u32 + 1;
+            let last = function.end.row as u32 + 1;
+
+            let mut traced = lines.range(first..=last).peekable();
+            if traced.peek().is_some() && traced.all(|(_, line)| !line.covered) {
+                functions.push(function);
+            }
+        }
+
+        // nested functions are part of the outer one
+        let outer = functions.clone();
+        functions.retain(|inner| {
+            !outer
+                .iter()
+                .any(|f| f != inner && f.start <= inner.start && inner.end <= f.end)
+        });
+    }
+
+    let mut ranges = functions
+        .iter()
+        .map(|function| Uncovered {
+            start: function.start,
+            end: function.end,
+            function: Some(function.name.clone()),
+        })
+        .collect::<Vec<_>>();
+
+    let statements = statements(&tree);
+
+    for (first, last) in runs(&syntax::functions(&tree, content), lines) {
+        let in_function = functions
+            .iter()
+            .any(|f| (f.start.row as u32) < first && last <= f.end.row as u32 + 1);
+        if in_function {
+            continue;
+        }
+
+        let Some(mut range) = line_range(&slices, first, last) else {
+            continue;
+        };
+
+        // stretch over statements starting in the run, but never onto a covered line
+        let limit = lines
+            .range(last + 1..)
+            .find(|(_, line)| line.covered)
+            .map(|(number, _)| *number - 1);
+
+        for (start, end) in &statements {
+            let starts_in_run = (first - 1..last).contains(&(start.row as u32));
+            let within_limit = limit.is_none_or(|limit| (end.row as u32) < limit);
+
+            if starts_in_run && within_limit && *end > range.end {
+                range.end = *end;
+            }
+        }
+
+        ranges.push(range);
+    }
+
+    ranges.sort_by_key(|range| range.start);
+    ranges
+}
+
+/// consecutive uncovered lines as first and last line, split where the enclosing function changes
+fn runs(functions: &[Function], lines: &BTreeMap<u32, Line>) -> Vec<(u32, u32)> {
+    let enclosing = |line: u32| {
+        functions
+            .iter()
+            .filter(|f| (f.start.row as u32) < line && line <= f.end.row as u32 + 1)
+            .min_by_key(|f| f.end.row - f.start.row)
+            .map(|f| f.start)
+    };
+
+    let mut runs = Vec::<(u32, u32)>::new();
+    let mut open = None;
+
+    for (number, line) in lines {
+        if line.covered {
+            open = None;
+            continue;
+        }
+
+        let owner = enclosing(*number);
+        match runs.last_mut() {
+            Some((_, last)) if open == Some(owner) => *last = *number,
+            _ => runs.push((*number, *number)),
+        }
+
+        open = Some(owner);
+    }
+
+    runs
+}
+
+/// from the first non blank character of `first` to the end of `last`, both one based
+fn line_range(slices: &[LineSlice], first: u32, last: u32) -> Option<Uncovered> {
+    let start = slices.get(first.checked_sub(1)? as usize)?;
+    let end = slices.get(last.checked_sub(1)? as usize)?;
+
+    Some(Uncovered {
+        start: Point::new(first as usize - 1, start.begin - start.start),
+        end: Point::new(last as usize - 1, end.end - end.start),
+        function: None,
+    })
+}
+
+/// where every statement like node starts and ends
+fn statements(tree: &Tree) -> Vec<(Point, Point)> {
+    let mut found = Vec::new();
+    collect(tree.root_node(), &mut found);
+    found
+}
+
+fn collect(node: Node, found: &mut Vec<(Point, Point)>) {
+    let mut cursor = node.walk();
+
+    for child in node.named_children(&mut cursor) {
+        let kind = child.kind();
+        let tail = node.kind() == "block" && kind.ends_with("_expression");
+
+        if kind.ends_with("_statement")
+            || kind.ends_with("_item")
+            || kind == "let_declaration"
+            || kind == "match_arm"
+            || tail
+        {
+            found.push((child.start_position(), child.end_position()));
+        }
+
+        collect(child, found);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        coverage::{Stats, Trace},
+        summary::lines,
+    };
+
+    use super::*;
+
+    const CONTENT: &str = r#"fn a(x: bool) -> u32 {
+    if x {
+        return 1;
+    }
+
+    let y = vec![
+        2,
+        3,
+    ];
+    y.len() as u32
+}
+
+fn b() {
+    a(true);
+}
+"#;
+
+    fn traces() -> BTreeMap<u32, Line> {
+        lines(&[
+            Trace::new(1, Stats::Line(1)),
+            Trace::new(2, Stats::Line(1)),
+            Trace::new(3, Stats::Line(1)),
+            Trace::new(6, Stats::Line(0)),
+            Trace::new(10, Stats::Line(0)),
+            Trace::new(13, Stats::Line(0)),
+            Trace::new(14, Stats::Line(0)),
+        ])
+    }
+
+    fn rows(ranges: &[Uncovered]) -> Vec<(usize, usize, Option<&str>)> {
+        ranges
+            .iter()
+            .map(|range| (range.start.row, range.end.row, range.function.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn test_line() {
+        let ranges = uncovered(CONTENT.as_bytes(), &traces(), Grouping::Line);
+        assert_eq!(
+            rows(&ranges),
+            vec![(5, 5, None), (9, 9, None), (12, 12, None), (13, 13, None)]
+        );
+    }
+
+    #[test]
+    fn test_block() {
+        let ranges = uncovered(CONTENT.as_bytes(), &traces(), Grouping::Block);
+        assert_eq!(rows(&ranges), vec![(5, 9, None), (12, 14, None)]);
+        assert_eq!(ranges[0].start, Point::new(5, 4));
+        assert_eq!(ranges[1].end, Point::new(14, 1));
+    }
+
+    #[test]
+    fn test_function() {
+        let ranges = uncovered(CONTENT.as_bytes(), &traces(), Grouping::Function);
+        assert_eq!(rows(&ranges), vec![(5, 9, None), (12, 14, Some("b"))]);
+    }
+}
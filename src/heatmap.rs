@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+
+use lsp_types::{SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensLegend};
+
+use crate::{line_slice::LineSlice, summary::Line};
+
+const COVERED: u32 = 0;
+const UNCOVERED: u32 = 1;
+const IGNORED: u32 = 2;
+
+const HOT: u32 = 1 << 0;
+
+/// the token types and modifiers themes can style, in the order tokens refer to them
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: vec![
+            SemanticTokenType::new("covered"),
+            SemanticTokenType::new("uncovered"),
+            SemanticTokenType::new("ignored"),
+        ],
+        token_modifiers: vec![SemanticTokenModifier::new("hot")],
+    }
+}
+
+/// a token over the text of every traced or ignored line, optionally only from `first` to `last`
+///
+/// lines are one based, lines hit at least `hot` times get the hot modifier
+pub fn tokens(
+    content: &[u8],
+    lines: &BTreeMap<u32, Line>,
+    ignored: &[u32],
+    hot: usize,
+    range: Option<(u32, u32)>,
+) -> Vec<SemanticToken> {
+    let mut kinds = lines
+        .iter()
+        .map(|(number, line)| {
+            let kind = if line.covered { COVERED } else { UNCOVERED };
+            let modifiers = match line.hits {
+                Some(hits) if hits >= hot => HOT,
+                _ => 0,
+            };
+
+            (*number, (kind, modifiers))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    for number in ignored {
+        kinds.entry(*number).or_insert((IGNORED, 0));
+    }
+
+    let (first, last) = range.unwrap_or((1, u32::MAX));
+    let slices = LineSlice::build(content);
+
+    let mut tokens = Vec::new();
+    let mut previous = 0;
+
+    for (number, (kind, modifiers)) in kinds.range(first..=last) {
+        let Some(line) = number.checked_sub(1) else {
+            continue;
+        };
+        let Some(slice) = slices.get(line as usize) else {
+            continue;
+        };
+
+        if slice.end == slice.begin {
+            continue;
+        }
+
+        tokens.push(SemanticToken {
+            delta_line: line - previous,
+            delta_start: (slice.begin - slice.start) as u32,
+            length: (slice.end - slice.begin) as u32,
+            token_type: *kind,
+            token_modifiers_bitset: *modifiers,
+        });
+
+        previous = line;
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        coverage::{Stats, Trace},
+        summary,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_tokens() {
+        const CONTENT: &[u8] = b"fn a() {\n    b();\n\n    c();\n}\n";
+
+        let lines = summary::lines(&[
+            Trace::new(1, Stats::Line(2000)),
+            Trace::new(2, Stats::Line(0)),
+            Trace::new(3, Stats::Line(0)),
+        ]);
+
+        let tokens = tokens(CONTENT, &lines, &[4, 1], 1000, None)
+            .iter()
+            .map(|t| {
+                (
+                    t.delta_line,
+                    t.delta_start,
+                    t.length,
+                    t.token_type,
+                    t.token_modifiers_bitset,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens,
+            vec![
+                (0, 0, 8, COVERED, HOT),
+                (1, 4, 4, UNCOVERED, 0),
+                (2, 4, 4, IGNORED, 0)
+            ]
+        );
+
+        let ranged = super::tokens(CONTENT, &lines, &[4], 1000, Some((2, 4)));
+        assert_eq!(ranged.len(), 2);
+        assert_eq!(ranged[0].delta_line, 1);
+
+        // a trace on line zero is broken, it is skipped instead of underflowing
+        let zero = summary::lines(&[Trace::new(0, Stats::Line(1))]);
+        assert!(super::tokens(CONTENT, &zero, &[0], 1000, Some((0, 4))).is_empty());
+    }
+}
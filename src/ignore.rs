@@ -1,12 +1,18 @@
-use std::{io::BufRead, path::Path};
+use std::{
+    io::BufRead,
+    path::{Path, PathBuf},
+};
 
 use eyre::ContextCompat;
 use glob::{MatchOptions, Pattern};
 use tracing::{debug, instrument};
-use tree_sitter::{Parser, Query, QueryCursor};
+use tree_sitter::{Node, Parser, Point, Query, QueryCursor};
 use tree_sitter_rust::language;
 
-use crate::coverage::Trace;
+use crate::{coverage::Trace, syntax};
+
+/// ignore files looked for in the project root
+const PROJECT_FILES: [&str; 2] = ["tarballin-ignore", ".tarballin-ignore"];
 
 #[derive(Default, PartialEq, Debug)]
 pub struct Ignore {
@@ -17,7 +23,8 @@ pub struct Ignore {
 pub enum IgnoreResult<'a> {
     Ignore,
     Apply,
-    Partial(&'a [Query]),
+    /// the queries of every rule that matched
+    Partial(Vec<&'a Query>),
 }
 
 impl<'a> IgnoreResult<'a> {
@@ -39,19 +46,14 @@ impl<'a> IgnoreResult<'a> {
                 let mut traces = traces.to_vec();
                 let mut rm_mark = vec![false; traces.len()];
 
-                for query in queries.iter() {
+                for query in queries {
                     let captures = cur.captures(query, node, content);
 
                     for (capt, _) in captures {
                         for sub in capt.captures {
                             for (i, trace) in traces.iter().enumerate() {
-                                if !rm_mark[i] {
-                                    let line = trace.line as usize;
-                                    if sub.node.start_position().row <= line
-                                        && line <= sub.node.end_position().row
-                                    {
-                                        rm_mark[i] = true;
-                                    }
+                                if !rm_mark[i] && spans(sub.node, trace.line) {
+                                    rm_mark[i] = true;
                                 }
                             }
                         }
@@ -70,25 +72,91 @@ impl<'a> IgnoreResult<'a> {
     }
 }
 
+/// whether a captured node hides a one based trace line, tree-sitter rows are zero based
+fn spans(node: Node, line: u32) -> bool {
+    let Some(row) = (line as usize).checked_sub(1) else {
+        return false;
+    };
+
+    node.start_position().row <= row && row <= node.end_position().row
+}
+
+/// a rule that can be appended to an ignore file
+#[derive(Debug, PartialEq)]
+pub struct Suggestion {
+    pub title: String,
+    /// the glob line, followed by an indented query line when it only covers part of the file
+    pub rule: String,
+}
+
 #[derive(PartialEq, Debug)]
 struct Rule {
     pattern: Pattern,
     queries: Vec<Query>,
+    /// the query text as written, to tell users which one matched
+    sources: Vec<String>,
 }
 
 impl Ignore {
+    /// what every rule matching the path amounts to, a whole file rule wins over queries
+    ///
+    /// rules appended for a file that an earlier glob already matches have to count as well
     #[instrument]
-    pub fn matches(&self, path: &Path) -> IgnoreResult {
+    pub fn matches(&self, path: &Path) -> IgnoreResult<'_> {
         debug!(path = %path.display(), "checking ignore");
-        for pat in &self.rules {
-            let result = pat.matches(path);
 
-            if !matches!(result, IgnoreResult::Apply) {
-                return result;
+        let mut queries = Vec::new();
+        for rule in self.rules.iter().filter(|rule| rule.matches(path)) {
+            if rule.queries.is_empty() {
+                return IgnoreResult::Ignore;
             }
+
+            queries.extend(&rule.queries);
         }
 
-        IgnoreResult::Apply
+        if queries.is_empty() {
+            IgnoreResult::Apply
+        } else {
+            IgnoreResult::Partial(queries)
+        }
+    }
+
+    /// describes the rule that hides `line` of the file, if any
+    pub fn reason(&self, path: &Path, content: &[u8], line: u32) -> eyre::Result<Option<String>> {
+        let rules = self
+            .rules
+            .iter()
+            .filter(|rule| rule.matches(path))
+            .collect::<Vec<_>>();
+
+        if let Some(rule) = rules.iter().find(|rule| rule.queries.is_empty()) {
+            return Ok(Some(format!("`{}`", rule.pattern)));
+        }
+
+        if rules.is_empty() {
+            return Ok(None);
+        }
+
+        let mut parser = Parser::new();
+        parser.set_language(language())?;
+        let tree = parser
+            .parse(content, None)
+            .with_context(|| "failed to parse tree")?;
+
+        let mut cur = QueryCursor::new();
+        for rule in rules {
+            for (query, source) in rule.queries.iter().zip(&rule.sources) {
+                let hides = cur
+                    .captures(query, tree.root_node(), content)
+                    .any(|(capt, _)| capt.captures.iter().any(|sub| spans(sub.node, line)));
+
+                if hides {
+                    return Ok(Some(format!("`{}` query `{source}`", rule.pattern)));
+                }
+            }
+        }
+
+        Ok(None)
     }
 
     fn parse(content: &[u8]) -> eyre::Result<Self> {
@@ -116,26 +184,136 @@ impl Ignore {
                 let query = Query::new(language(), content)?;
 
                 rule.queries.push(query);
+                rule.sources.push(content.to_string());
             } else {
                 let content = content.trim();
                 let pattern = Pattern::new(content)?;
-                let queries = vec![];
-
-                rules.push(Rule { pattern, queries });
+                rules.push(Rule {
+                    pattern,
+                    queries: vec![],
+                    sources: vec![],
+                });
             }
         }
 
         Ok(Self { rules })
     }
 
+    /// adds rules written in the ignore file syntax after the existing ones
+    pub fn extend(&mut self, content: &[u8]) -> eyre::Result<()> {
+        *self += Self::parse(content)?;
+        Ok(())
+    }
+
     pub fn load(path: &Path) -> eyre::Result<Self> {
         let content = std::fs::read(path)?;
         Self::parse(&content)
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.rules.is_empty()
+    /// the rules of every ignore file in the project root, in the order they are read
+    pub fn project(root: &Path) -> Self {
+        let mut ignore = Ignore::default();
+
+        for name in PROJECT_FILES {
+            match Ignore::load(&root.join(name)) {
+                Ok(project) => ignore += project,
+                Err(error) => debug!(%error, name, "no ignore rules loaded"),
+            }
+        }
+
+        ignore
+    }
+}
+
+/// the ignore file of the project new rules go to, the first one there is or the default
+pub fn project_file(root: &Path) -> PathBuf {
+    PROJECT_FILES
+        .iter()
+        .map(|name| root.join(name))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| root.join(PROJECT_FILES[0]))
+}
+
+pub fn is_project_file(root: &Path, path: &Path) -> bool {
+    PROJECT_FILES.iter().any(|name| root.join(name) == path)
+}
+
+/// rules for the function and `impl` block around `point`, and for the whole file at `path`
+pub fn suggest(content: &[u8], path: &Path, point: Point) -> eyre::Result<Vec<Suggestion>> {
+    let tree = syntax::parse(content)?;
+    let glob = Pattern::escape(&path.to_string_lossy());
+
+    let mut function = None;
+    let mut block = None;
+
+    let mut node = tree.root_node().descendant_for_point_range(point, point);
+    while let Some(current) = node {
+        match current.kind() {
+            "function_item" if function.is_none() => function = Some(current),
+            "impl_item" if block.is_none() => block = Some(current),
+            _ => (),
+        }
+
+        node = current.parent();
+    }
+
+    let text = |node: Node| node.utf8_text(content).unwrap_or_default().to_string();
+    let mut suggestions = Vec::new();
+
+    if let Some(name) = function.and_then(|f| f.child_by_field_name("name")) {
+        suggestions.push(Suggestion {
+            title: "Ignore this function".to_string(),
+            rule: format!(
+                "{glob}\n    ((function_item name: (identifier) @id) (#eq? @id {:?})) @query\n",
+                text(name)
+            ),
+        });
+    }
+
+    if let Some(query) = block.and_then(|block| impl_query(block, &text)) {
+        suggestions.push(Suggestion {
+            title: "Ignore this impl block".to_string(),
+            rule: format!("{glob}\n    {query}\n"),
+        });
     }
+
+    suggestions.push(Suggestion {
+        title: "Ignore this file".to_string(),
+        rule: format!("{glob}\n"),
+    });
+
+    Ok(suggestions)
+}
+
+/// matches `impl` blocks by their type, and by their trait when it is a plain name
+fn impl_query(block: Node, text: &impl Fn(Node) -> String) -> Option<String> {
+    let ty = block.child_by_field_name("type")?;
+    let (pattern, name) = match ty.kind() {
+        "type_identifier" => ("(type_identifier) @id".to_string(), text(ty)),
+        "generic_type" => {
+            let inner = ty
+                .child_by_field_name("type")
+                .filter(|inner| inner.kind() == "type_identifier")?;
+            (
+                "(generic_type type: (type_identifier) @id)".to_string(),
+                text(inner),
+            )
+        }
+        _ => return None,
+    };
+
+    let query = match block
+        .child_by_field_name("trait")
+        .filter(|t| t.kind() == "type_identifier")
+    {
+        Some(t) => format!(
+            "((impl_item trait: (type_identifier) @trait type: {pattern}) (#eq? @trait {:?}) (#eq? @id {name:?})) @query",
+            text(t)
+        ),
+        None => format!("((impl_item type: {pattern}) (#eq? @id {name:?})) @query"),
+    };
+
+    Some(query)
 }
 
 // yes I know I'm the worst
@@ -146,22 +324,14 @@ impl std::ops::AddAssign for Ignore {
 }
 
 impl Rule {
-    pub fn matches(&self, path: &Path) -> IgnoreResult {
+    fn matches(&self, path: &Path) -> bool {
         let opts = MatchOptions {
             case_sensitive: true,
             require_literal_separator: true,
             require_literal_leading_dot: true,
         };
 
-        if self.pattern.matches_path_with(path, opts) {
-            if self.queries.is_empty() {
-                IgnoreResult::Ignore
-            } else {
-                IgnoreResult::Partial(&self.queries)
-            }
-        } else {
-            IgnoreResult::Apply
-        }
+        self.pattern.matches_path_with(path, opts)
     }
 }
 
@@ -219,6 +389,145 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_reason() {
+        const CONTENT: &[u8] = b"src/main.rs\n\t((function_item name: (identifier) @id) (#eq? @id \"main\")) @query\nsrc/gen.rs\n";
+        const SOURCE: &[u8] = b"fn helper() {}\n\nfn main() {\n    helper();\n}\n";
+
+        let ignore = Ignore::parse(CONTENT).unwrap();
+
+        assert_eq!(
+            ignore
+                .reason(Path::new("src/main.rs"), SOURCE, 3)
+                .unwrap()
+                .as_deref(),
+            Some("`src/main.rs` query `((function_item name: (identifier) @id) (#eq? @id \"main\")) @query`")
+        );
+        assert_eq!(
+            ignore.reason(Path::new("src/main.rs"), SOURCE, 0).unwrap(),
+            None
+        );
+        assert_eq!(
+            ignore
+                .reason(Path::new("src/gen.rs"), SOURCE, 1)
+                .unwrap()
+                .as_deref(),
+            Some("`src/gen.rs`")
+        );
+    }
+
+    const MERGED_SOURCE: &[u8] =
+        b"fn a() {\n    x();\n}\n\nfn b() {\n    x();\n}\n\nfn c() {\n    x();\n}\n";
+
+    fn kept(ignore: &Ignore, path: &str) -> Vec<u32> {
+        let traces = [2, 6, 10]
+            .into_iter()
+            .map(|line| Trace::new(line, crate::coverage::Stats::Line(0)))
+            .collect::<Vec<_>>();
+
+        ignore
+            .matches(Path::new(path))
+            .filter(MERGED_SOURCE, &traces)
+            .unwrap()
+            .iter()
+            .map(|trace| trace.line)
+            .collect()
+    }
+
+    #[test]
+    fn test_match_merged() {
+        const CONTENT: &[u8] = b"src/*.rs\n\t((function_item name: (identifier) @id) (#eq? @id \"a\")) @query\nsrc/lib.rs\n\t((function_item name: (identifier) @id) (#eq? @id \"b\")) @query\n";
+
+        let ignore = Ignore::parse(CONTENT).unwrap();
+
+        // the queries of both rules apply, the first matching rule used to be the only one
+        assert_eq!(kept(&ignore, "src/lib.rs"), vec![10]);
+        // a path only one rule matches is the same as before
+        assert_eq!(kept(&ignore, "src/main.rs"), vec![6, 10]);
+        assert!(ignore
+            .reason(Path::new("src/lib.rs"), MERGED_SOURCE, 6)
+            .unwrap()
+            .is_some_and(|reason| reason.starts_with("`src/lib.rs`")));
+    }
+
+    #[test]
+    fn test_match_whole_file_wins() {
+        const CONTENT: &[u8] = b"src/lib.rs\n\t((function_item name: (identifier) @id) (#eq? @id \"a\")) @query\nsrc/*.rs\n";
+
+        let ignore = Ignore::parse(CONTENT).unwrap();
+
+        // the earlier query rule used to decide on its own and keep the rest of the file
+        let res = ignore.matches(Path::new("src/lib.rs"));
+        assert!(matches!(res, IgnoreResult::Ignore), "found {res:?}");
+        assert!(kept(&ignore, "src/lib.rs").is_empty());
+        assert_eq!(
+            ignore
+                .reason(Path::new("src/lib.rs"), MERGED_SOURCE, 10)
+                .unwrap()
+                .as_deref(),
+            Some("`src/*.rs`")
+        );
+    }
+
+    #[test]
+    fn test_match_lines() {
+        const CONTENT: &[u8] =
+            b"src/lib.rs\n\t((function_item name: (identifier) @id) (#eq? @id \"b\")) @query\n";
+        const SOURCE: &[u8] = b"fn a() {}\n\nfn b() {\n    x();\n}\n\nfn c() {}\n";
+
+        let ignore = Ignore::parse(CONTENT).unwrap();
+        let traces = (0..=7)
+            .map(|line| Trace::new(line, crate::coverage::Stats::Line(0)))
+            .collect::<Vec<_>>();
+
+        let kept = ignore
+            .matches(Path::new("src/lib.rs"))
+            .filter(SOURCE, &traces)
+            .unwrap()
+            .iter()
+            .map(|trace| trace.line)
+            .collect::<Vec<_>>();
+
+        // `fn b` is on lines 3 to 5, it used to hide line 2 above it and keep its closing brace
+        assert_eq!(kept, vec![0, 1, 2, 6, 7]);
+    }
+
+    #[test]
+    fn test_suggest() {
+        const SOURCE: &[u8] = b"struct Parser<T>(T);\n\nimpl<T> Parser<T> {\n    fn parse(&self) {\n        todo!()\n    }\n}\n\nimpl std::fmt::Display for Parser<u8> {\n    fn fmt(&self, _: &mut std::fmt::Formatter) -> std::fmt::Result {\n        Ok(())\n    }\n}\n";
+
+        let path = Path::new("src/[gen].rs");
+        let suggestions = suggest(SOURCE, path, Point::new(4, 8)).unwrap();
+        let rules = suggestions
+            .iter()
+            .map(|s| s.rule.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            rules,
+            vec![
+                "src/[[]gen[]].rs\n    ((function_item name: (identifier) @id) (#eq? @id \"parse\")) @query\n",
+                "src/[[]gen[]].rs\n    ((impl_item type: (generic_type type: (type_identifier) @id)) (#eq? @id \"Parser\")) @query\n",
+                "src/[[]gen[]].rs\n",
+            ]
+        );
+
+        // every rule has to parse, and the function rule hides exactly the function
+        let mut ignore = Ignore::default();
+        ignore.extend(rules[0].as_bytes()).unwrap();
+        ignore.extend(rules[1].as_bytes()).unwrap();
+        assert_eq!(
+            ignore.reason(path, SOURCE, 5).unwrap().as_deref(),
+            Some("`src/[[]gen[]].rs` query `((function_item name: (identifier) @id) (#eq? @id \"parse\")) @query`")
+        );
+        assert_eq!(ignore.reason(path, SOURCE, 1).unwrap(), None);
+
+        let display = suggest(SOURCE, path, Point::new(10, 8)).unwrap();
+        assert_eq!(display[1].title, "Ignore this impl block");
+        assert!(Ignore::parse(display[1].rule.as_bytes()).is_ok());
+        assert!(display[1].rule.contains("(#eq? @id \"Parser\")"));
+    }
+
     #[test]
     fn test_match_partial() {
         const CONTENT: &[u8] =
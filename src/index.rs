@@ -0,0 +1,338 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::{self, File},
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use crossbeam_channel::{select, Receiver, Sender};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::{
+    config::RunnerConfig,
+    coverage::{Coverage, Source},
+    runner::{Job, Output},
+};
+
+/// how often a running test is checked on
+const POLL: Duration = Duration::from_millis(200);
+
+/// which tests execute each line, one based
+#[derive(Deserialize, Serialize, Default, Debug, PartialEq)]
+pub struct Index {
+    /// of what the index was built from, a different one means it is stale
+    #[serde(default)]
+    fingerprint: u64,
+    tests: Vec<String>,
+    /// positions in `tests` per line
+    files: HashMap<PathBuf, BTreeMap<u32, Vec<usize>>>,
+}
+
+/// what is needed to run the tests one by one, away from the main runs
+pub struct Indexer {
+    pub runner: RunnerConfig,
+    /// a target directory of its own, so the reports of the main runs are left alone
+    pub target: PathBuf,
+    pub source: Source,
+    /// where the sources the index depends on are
+    pub root: PathBuf,
+}
+
+pub enum Update {
+    /// the index of an earlier session still matches the sources
+    Loaded(Index),
+    /// a test was run and indexed
+    Indexed(String),
+    Done(Index),
+}
+
+impl Index {
+    fn load(path: &Path) -> Option<Self> {
+        let file = File::open(path).ok()?;
+        serde_json::from_reader(file).ok()
+    }
+
+    fn save(&self, path: &Path) -> eyre::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = File::create(path)?;
+        serde_json::to_writer(file, self)?;
+
+        Ok(())
+    }
+
+    /// records every line the coverage of a single test covers
+    pub fn add(&mut self, test: &str, coverage: &Coverage) {
+        let position = self.tests.len();
+        self.tests.push(test.to_string());
+
+        for (path, traces) in &coverage.traces {
+            let lines = self.files.entry(path.clone()).or_default();
+
+            for trace in traces.iter().filter(|trace| trace.stats.covered()) {
+                let tests = lines.entry(trace.line).or_default();
+                if tests.last() != Some(&position) {
+                    tests.push(position);
+                }
+            }
+        }
+    }
+
+    /// the tests that execute a line
+    pub fn tests(&self, path: &Path, line: u32) -> Vec<String> {
+        self.files
+            .get(path)
+            .and_then(|lines| lines.get(&line))
+            .map(|tests| tests.iter().map(|i| self.tests[*i].clone()).collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Indexer {
+    /// sends the stored index if it is still current, otherwise runs every test on its own
+    /// and sends the finished index
+    ///
+    /// stops early once nobody listens, and as soon as `cancel` disconnects
+    pub fn run(self, tx: Sender<Update>, cancel: Receiver<()>) {
+        let fingerprint = self.fingerprint();
+        if let Some(index) =
+            Index::load(&self.path()).filter(|index| index.fingerprint == fingerprint)
+        {
+            debug!("reusing the stored index");
+            let _ = tx.send(Update::Loaded(index));
+            return;
+        }
+
+        let tests = match self.list(&cancel) {
+            Ok(tests) => tests,
+            Err(error) => {
+                warn!(%error, "failed to list tests to index");
+                return;
+            }
+        };
+
+        debug!(tests = tests.len(), "indexing tests");
+
+        let mut index = Index {
+            fingerprint,
+            ..Index::default()
+        };
+        for test in tests {
+            match self.cover(&test, &cancel) {
+                Ok(coverage) => index.add(&test, &coverage),
+                Err(_) if cancel.try_recv().is_err_and(|e| e.is_disconnected()) => return,
+                Err(error) => warn!(%error, test, "failed to index test"),
+            }
+
+            if tx.send(Update::Indexed(test)).is_err() {
+                return;
+            }
+        }
+
+        if let Err(error) = index.save(&self.path()) {
+            warn!(%error, "failed to save index");
+        }
+
+        let _ = tx.send(Update::Done(index));
+    }
+
+    /// where the index is kept between sessions
+    fn path(&self) -> PathBuf {
+        self.target.join("tarballin-index.json")
+    }
+
+    /// of the sources, the manifests and how the tests get run
+    fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        format!("{:?}", self.runner.command(&self.target, None, false)).hash(&mut hasher);
+
+        let mut files = Vec::new();
+        sources(&self.root, &mut files);
+        files.sort();
+
+        for path in files {
+            path.hash(&mut hasher);
+            fs::read(&path).ok().hash(&mut hasher);
+        }
+
+        hasher.finish()
+    }
+
+    fn list(&self, cancel: &Receiver<()>) -> eyre::Result<Vec<String>> {
+        let output = wait(self.runner.list_command(&self.target.join("list")), cancel)?;
+
+        Ok(tests(&output.stdout))
+    }
+
+    /// the coverage of exactly one test
+    fn cover(&self, test: &str, cancel: &Receiver<()>) -> eyre::Result<Coverage> {
+        let mut command = self.runner.command(&self.target, Some(test), false);
+        command.arg("--exact");
+        wait(command, cancel)?;
+
+        Ok(self.source.load()?)
+    }
+}
+
+/// the output of a successful command, which is killed once `cancel` disconnects
+fn wait(command: Command, cancel: &Receiver<()>) -> eyre::Result<Output> {
+    let mut job = Job::start(command)?;
+
+    loop {
+        if let Some(status) = job.try_wait()? {
+            eyre::ensure!(status.success(), "exited with {status}");
+            return Ok(job.output());
+        }
+
+        select! {
+            recv(cancel) -> _ => eyre::bail!("indexing was cancelled"),
+            default(POLL) => (),
+        }
+    }
+}
+
+/// every rust source and manifest below `dir`, build output and hidden directories left out
+fn sources(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+
+        if kind.is_dir() {
+            let built = name == "target" || path.join("CACHEDIR.TAG").exists();
+            if !built && !name.to_string_lossy().starts_with('.') {
+                sources(&path, files);
+            }
+        } else if path.extension().is_some_and(|ext| ext == "rs")
+            || name == "Cargo.toml"
+            || name == "Cargo.lock"
+        {
+            files.push(path);
+        }
+    }
+}
+
+/// the test names in a terse libtest listing, doc tests can't be picked on their own and are left out
+fn tests(listing: &str) -> Vec<String> {
+    let mut tests = listing
+        .lines()
+        .filter_map(|line| line.strip_suffix(": test"))
+        .filter(|name| !name.contains(char::is_whitespace))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+
+    tests.sort();
+    tests.dedup();
+    tests
+}
+
+#[cfg(test)]
+mod test {
+    use crossbeam_channel::bounded;
+    use tempdir::TempDir;
+
+    use crate::coverage::{Stats, Trace};
+
+    use super::*;
+
+    fn indexer(root: &Path) -> Indexer {
+        let target = root.join("target").join("index");
+
+        Indexer {
+            runner: RunnerConfig::default(),
+            source: Source::Tarpaulin {
+                package: "demo".to_string(),
+                target: target.clone(),
+            },
+            target,
+            root: root.to_path_buf(),
+        }
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let dir = TempDir::new("tarballin-index").unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::create_dir_all(dir.path().join("target").join("debug")).unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "[package]").unwrap();
+        fs::write(dir.path().join("src").join("lib.rs"), "fn a() {}").unwrap();
+
+        let indexer = indexer(dir.path());
+        let fingerprint = indexer.fingerprint();
+
+        // build output doesn't make the index stale
+        fs::write(dir.path().join("target/debug/out.rs"), "fn b() {}").unwrap();
+        assert_eq!(indexer.fingerprint(), fingerprint);
+
+        fs::write(dir.path().join("src").join("lib.rs"), "fn b() {}").unwrap();
+        assert_ne!(indexer.fingerprint(), fingerprint);
+    }
+
+    #[test]
+    fn test_reuse() {
+        let dir = TempDir::new("tarballin-index").unwrap();
+        fs::write(dir.path().join("Cargo.toml"), "[package]").unwrap();
+
+        let indexer = indexer(dir.path());
+        let stored = Index {
+            fingerprint: indexer.fingerprint(),
+            tests: vec!["a".to_string()],
+            files: HashMap::new(),
+        };
+        stored.save(&indexer.path()).unwrap();
+
+        let (tx, rx) = bounded(1);
+        let (_cancel, cancel) = bounded(0);
+        indexer.run(tx, cancel);
+
+        let Ok(Update::Loaded(index)) = rx.recv() else {
+            panic!("stored index was not reused");
+        };
+        assert_eq!(index, stored);
+    }
+
+    #[test]
+    fn test_tests() {
+        const LISTING: &str = "cli::test::test_args: test\nsummary::test::test_uncovered: test\nbench_parse: bench\ncli::test::test_args: test\nsrc/lib.rs - parse (line 12): test\n";
+
+        assert_eq!(
+            tests(LISTING),
+            vec!["cli::test::test_args", "summary::test::test_uncovered"]
+        );
+    }
+
+    #[test]
+    fn test_index() {
+        let path = PathBuf::from("/project/src/lib.rs");
+        let coverage = |lines: &[(u32, usize)]| Coverage {
+            traces: HashMap::from([(
+                path.clone(),
+                lines
+                    .iter()
+                    .map(|(line, hits)| Trace::new(*line, Stats::Line(*hits)))
+                    .collect(),
+            )]),
+        };
+
+        let mut index = Index::default();
+        index.add("a", &coverage(&[(1, 1), (2, 0), (3, 4)]));
+        index.add("b", &coverage(&[(1, 2), (2, 1)]));
+
+        assert_eq!(index.tests(&path, 1), vec!["a", "b"]);
+        assert_eq!(index.tests(&path, 2), vec!["b"]);
+        assert_eq!(index.tests(&path, 3), vec!["a"]);
+        assert!(index.tests(&path, 4).is_empty());
+        assert!(index.tests(Path::new("/other.rs"), 1).is_empty());
+    }
+}
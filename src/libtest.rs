@@ -0,0 +1,413 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, Position, Range,
+};
+use tracing::debug;
+use url::Url;
+
+use crate::{
+    line_slice::LineSlice,
+    syntax::{self, Function},
+};
+
+/// where test functions may live, relative to the project root
+const SOURCES: [&str; 6] = [
+    "src/**/*.rs",
+    "tests/**/*.rs",
+    "benches/**/*.rs",
+    "examples/**/*.rs",
+    "*/src/**/*.rs",
+    "*/tests/**/*.rs",
+];
+
+/// a test that failed during a coverage run
+#[derive(Debug, PartialEq)]
+pub struct Failure {
+    pub test: String,
+    pub message: String,
+    pub panic: Option<PanicLocation>,
+}
+
+/// a `file:line:col` from panic output, one based like the compiler prints it
+#[derive(Debug, PartialEq)]
+pub struct PanicLocation {
+    pub file: PathBuf,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// picks the failed tests out of libtest's human readable output
+pub fn failures(output: &str) -> Vec<Failure> {
+    let mut failed = Vec::<String>::new();
+    let mut sections = HashMap::<String, Vec<&str>>::new();
+    let mut current: Option<&mut Vec<&str>> = None;
+
+    for line in output.lines() {
+        if let Some(name) = line
+            .strip_prefix("test ")
+            .and_then(|rest| rest.strip_suffix(" ... FAILED"))
+        {
+            let name = name.strip_suffix(" - should panic").unwrap_or(name);
+            if !failed.iter().any(|f| f == name) {
+                failed.push(name.to_string());
+            }
+            continue;
+        }
+
+        if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|rest| rest.strip_suffix(" stdout ----"))
+        {
+            current = Some(sections.entry(name.to_string()).or_default());
+            continue;
+        }
+
+        if line == "failures:" || line.starts_with("test result:") {
+            current = None;
+            continue;
+        }
+
+        if let Some(section) = current.as_mut() {
+            section.push(line);
+        }
+    }
+
+    failed
+        .into_iter()
+        .map(|test| {
+            let (message, panic) = match sections.get(&test) {
+                Some(lines) => parse_section(lines),
+                None => (String::new(), None),
+            };
+
+            let message = if message.is_empty() {
+                "test failed".to_string()
+            } else {
+                message
+            };
+
+            Failure {
+                test,
+                message,
+                panic,
+            }
+        })
+        .collect()
+}
+
+fn parse_section(lines: &[&str]) -> (String, Option<PanicLocation>) {
+    let Some(at) = lines.iter().position(|line| line.contains(" panicked at ")) else {
+        let message = lines
+            .iter()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        return (message, None);
+    };
+
+    let (_, rest) = lines[at].split_once(" panicked at ").unwrap_or_default();
+
+    // before rust 1.73 the message came first: panicked at 'msg', src/lib.rs:1:2
+    if let Some(quoted) = rest.strip_prefix('\'') {
+        if let Some((message, location)) = quoted.rsplit_once("', ") {
+            return (message.to_string(), parse_location(location));
+        }
+    }
+
+    let panic = parse_location(rest.trim_end_matches(':'));
+
+    let message = lines[at + 1..]
+        .iter()
+        .take_while(|line| {
+            !line.is_empty()
+                && !line.starts_with("note: run with `RUST_BACKTRACE")
+                && !line.starts_with("stack backtrace:")
+        })
+        .copied()
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    (message, panic)
+}
+
+fn parse_location(location: &str) -> Option<PanicLocation> {
+    let mut parts = location.trim().rsplitn(3, ':');
+    let column = parts.next()?.parse().ok()?;
+    let line = parts.next()?.parse().ok()?;
+    let file = PathBuf::from(parts.next()?);
+
+    Some(PanicLocation { file, line, column })
+}
+
+/// diagnostics on the failing assertion and on the test function, linked to each other
+pub fn diagnostics(failures: &[Failure], root: &Path) -> HashMap<PathBuf, Vec<Diagnostic>> {
+    let mut sources = Sources::default();
+    let mut diags = HashMap::<PathBuf, Vec<Diagnostic>>::new();
+
+    for failure in failures {
+        let panic = failure.panic.as_ref().and_then(|panic| {
+            let path = root.join(&panic.file);
+            let range = sources.line_range(&path, panic.line, panic.column)?;
+            let uri = Url::from_file_path(&path).ok()?;
+
+            Some((path, Location { uri, range }))
+        });
+
+        let preferred = panic.as_ref().map(|(path, _)| path.as_path());
+        let test = sources
+            .find_test(root, &failure.test, preferred)
+            .and_then(|(path, f)| {
+                let uri = Url::from_file_path(&path).ok()?;
+                let range = Range::new(
+                    Position::new(f.name_start.row as u32, f.name_start.column as u32),
+                    Position::new(f.name_end.row as u32, f.name_end.column as u32),
+                );
+
+                Some((path, Location { uri, range }))
+            });
+
+        if let Some((path, location)) = &panic {
+            let related = test.as_ref().map(|(_, test_location)| {
+                vec![DiagnosticRelatedInformation {
+                    location: test_location.clone(),
+                    message: format!("in test `{}`", failure.test),
+                }]
+            });
+
+            diags.entry(path.clone()).or_default().push(Diagnostic {
+                range: location.range,
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("tarballin".to_string()),
+                message: format!("test `{}` panicked: {}", failure.test, failure.message),
+                related_information: related,
+                ..Diagnostic::default()
+            });
+        }
+
+        if let Some((path, location)) = &test {
+            let related = panic.as_ref().map(|(_, panic_location)| {
+                vec![DiagnosticRelatedInformation {
+                    location: panic_location.clone(),
+                    message: "panicked here".to_string(),
+                }]
+            });
+
+            diags.entry(path.clone()).or_default().push(Diagnostic {
+                range: location.range,
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("tarballin".to_string()),
+                message: format!("test `{}` failed: {}", failure.test, failure.message),
+                related_information: related,
+                ..Diagnostic::default()
+            });
+        }
+
+        if panic.is_none() && test.is_none() {
+            debug!(test = failure.test, "could not locate failed test");
+        }
+    }
+
+    diags
+}
+
+/// source files read while locating failures, each parsed at most once
+#[derive(Default)]
+struct Sources {
+    files: HashMap<PathBuf, Option<Source>>,
+}
+
+struct Source {
+    content: Vec<u8>,
+    functions: Vec<Function>,
+}
+
+impl Sources {
+    fn load(&mut self, path: &Path) -> Option<&Source> {
+        self.files
+            .entry(path.to_path_buf())
+            .or_insert_with(|| {
+                let content = std::fs::read(path).ok()?;
+                let tree = syntax::parse(&content).ok()?;
+                let functions = syntax::functions(&tree, &content);
+
+                Some(Source { content, functions })
+            })
+            .as_ref()
+    }
+
+    /// from the panic column to the end of the line
+    fn line_range(&mut self, path: &Path, line: u32, column: u32) -> Option<Range> {
+        let source = self.load(path)?;
+        let line = line.checked_sub(1)?;
+        let slice = LineSlice::build(&source.content)
+            .into_iter()
+            .nth(line as usize)?;
+
+        Some(Range::new(
+            Position::new(line, column.saturating_sub(1)),
+            Position::new(line, (slice.end - slice.start) as u32),
+        ))
+    }
+
+    /// the `#[test]` function for a libtest name like `module::tests::name`
+    fn find_test(
+        &mut self,
+        root: &Path,
+        test: &str,
+        preferred: Option<&Path>,
+    ) -> Option<(PathBuf, Function)> {
+        let mut segments = test.split("::").collect::<Vec<_>>();
+        let name = segments.pop()?;
+
+        let mut candidates = preferred
+            .map(Path::to_path_buf)
+            .into_iter()
+            .collect::<Vec<_>>();
+        for pattern in SOURCES {
+            let pattern = root.join(pattern);
+            let Ok(paths) = glob::glob(&pattern.to_string_lossy()) else {
+                continue;
+            };
+
+            candidates.extend(paths.flatten());
+        }
+
+        for path in candidates {
+            let Some(source) = self.load(&path) else {
+                continue;
+            };
+
+            let found = source
+                .functions
+                .iter()
+                .find(|f| f.test && f.name == name && ends_with(&segments, &f.modules));
+
+            if let Some(found) = found {
+                return Some((path, found.clone()));
+            }
+        }
+
+        None
+    }
+}
+
+fn ends_with(segments: &[&str], modules: &[String]) -> bool {
+    modules.len() <= segments.len()
+        && segments[segments.len() - modules.len()..]
+            .iter()
+            .zip(modules)
+            .all(|(segment, module)| segment == module)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const OUTPUT: &str = r#"
+running 3 tests
+test parser::tests::parses ... ok
+test parser::tests::rejects ... FAILED
+test legacy ... FAILED
+test should_panic_test - should panic ... FAILED
+
+failures:
+
+---- parser::tests::rejects stdout ----
+thread 'parser::tests::rejects' (4242) panicked at src/parser.rs:42:9:
+assertion `left == right` failed
+  left: 1
+ right: 2
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+---- legacy stdout ----
+thread 'legacy' panicked at 'explicit panic', tests/legacy.rs:3:5
+
+---- should_panic_test stdout ----
+note: test did not panic as expected
+
+failures:
+    parser::tests::rejects
+    legacy
+    should_panic_test
+
+test result: FAILED. 1 passed; 3 failed; 0 ignored; 0 measured; 0 filtered out
+"#;
+
+    #[test]
+    fn test_failures() {
+        assert_eq!(
+            failures(OUTPUT),
+            vec![
+                Failure {
+                    test: "parser::tests::rejects".to_string(),
+                    message: "assertion `left == right` failed\n  left: 1\n right: 2".to_string(),
+                    panic: Some(PanicLocation {
+                        file: PathBuf::from("src/parser.rs"),
+                        line: 42,
+                        column: 9,
+                    }),
+                },
+                Failure {
+                    test: "legacy".to_string(),
+                    message: "explicit panic".to_string(),
+                    panic: Some(PanicLocation {
+                        file: PathBuf::from("tests/legacy.rs"),
+                        line: 3,
+                        column: 5,
+                    }),
+                },
+                Failure {
+                    test: "should_panic_test".to_string(),
+                    message: "note: test did not panic as expected".to_string(),
+                    panic: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let root = tempdir::TempDir::new("tarballin-libtest").unwrap();
+        std::fs::create_dir(root.path().join("src")).unwrap();
+        std::fs::write(
+            root.path().join("src/parser.rs"),
+            "pub fn parse() {}\n\n#[cfg(test)]\nmod tests {\n    #[test]\n    fn rejects() {\n        assert_eq!(1, 2);\n    }\n}\n",
+        )
+        .unwrap();
+
+        let failures = vec![Failure {
+            test: "parser::tests::rejects".to_string(),
+            message: "assertion failed".to_string(),
+            panic: Some(PanicLocation {
+                file: PathBuf::from("src/parser.rs"),
+                line: 7,
+                column: 9,
+            }),
+        }];
+
+        let diags = diagnostics(&failures, root.path());
+        let file = &diags[&root.path().join("src/parser.rs")];
+
+        assert_eq!(file.len(), 2);
+        assert_eq!(
+            file[0].range,
+            Range::new(Position::new(6, 8), Position::new(6, 25))
+        );
+        assert_eq!(
+            file[1].range,
+            Range::new(Position::new(5, 7), Position::new(5, 14))
+        );
+        assert_eq!(
+            file[0].related_information.as_ref().unwrap()[0]
+                .location
+                .range,
+            file[1].range
+        );
+    }
+}
@@ -0,0 +1,61 @@
+//! requests beyond the language server protocol
+
+use lsp_types::{request::Request, Range, TextDocumentIdentifier, TextDocumentPositionParams};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// the `workspace/executeCommand` that starts a coverage run, optionally with a test filter argument
+pub const RUN: &str = "tarballin.run";
+
+/// runs only the tests matching its argument and shows what they cover, next to the full coverage,
+/// an optional second `true` argument runs only the test with exactly that name
+pub const RUN_TEST: &str = "tarballin.runTest";
+
+/// adds the ignore rule given as its argument to the rules in use, the code actions write it to the
+/// ignore file as well
+pub const ADD_IGNORE: &str = "tarballin.addIgnore";
+
+/// drops the coverage of a single test and goes back to the full coverage
+pub const SHOW_ALL: &str = "tarballin.showAll";
+
+/// per function line coverage of a document, or of everything covered without one
+pub enum Functions {}
+
+impl Request for Functions {
+    type Params = FunctionsParams;
+    type Result = Vec<FunctionCoverage>;
+    const METHOD: &'static str = "tarballin/functions";
+}
+
+/// the names of the tests that execute a line, from the index
+pub enum CoveredBy {}
+
+impl Request for CoveredBy {
+    type Params = TextDocumentPositionParams;
+    type Result = Vec<String>;
+    const METHOD: &'static str = "tarballin/coveredBy";
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionsParams {
+    pub text_document: Option<TextDocumentIdentifier>,
+}
+
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionCoverage {
+    pub uri: Url,
+    /// qualified with the `impl` type for methods
+    pub name: String,
+    /// the whole function item
+    pub range: Range,
+    /// the function signature up to the name, where its diagnostic sits
+    pub selection_range: Range,
+    pub covered: usize,
+    pub total: usize,
+    pub percent: f64,
+    /// the name the coverage tool reported
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reported_name: Option<String>,
+}
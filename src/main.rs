@@ -1,19 +1,33 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::Parser;
 use crossbeam_channel::bounded;
-use lsp_server::Connection;
+use lsp_server::{ErrorCode, Message, Response};
 use lsp_types::InitializeParams;
-use tracing::{debug, info, info_span, trace};
+use tracing::{debug, error, info, info_span, trace};
 
-use crate::ignore::Ignore;
+use crate::{config::Config, coverage::Coverage, ignore::Ignore, target::TargetDir};
 
+mod annotate;
+mod check;
 mod cli;
+mod compiler;
+mod config;
 mod coverage;
+mod diff;
+mod group;
+mod heatmap;
 mod ignore;
+mod index;
+mod libtest;
 mod line_slice;
+mod lsp_ext;
 mod mode;
 mod runner;
+mod summary;
+mod syntax;
+mod target;
+mod transport;
 mod workers;
 
 #[derive(thiserror::Error, Debug)]
@@ -29,6 +43,9 @@ pub enum Error {
 
     #[error("{0}")]
     Parse(#[from] url::ParseError),
+
+    #[error("invalid {0} coverage: {1}")]
+    Format(&'static str, String),
 }
 
 fn main() {
@@ -38,15 +55,190 @@ fn main() {
     let span = info_span!("main");
     let _guard = span.enter();
 
-    let (conn, threads) = match args.connect {
-        cli::Conn::Stdio => {
-            info!("planning on connecting over stdio");
+    match args.command {
+        None => serve(args.serve),
+        Some(cli::Command::Serve(serve_args)) => serve(serve_args),
+        Some(cli::Command::Report(report_args)) => report(report_args),
+        Some(cli::Command::Check(check_args)) => check(check_args),
+        Some(cli::Command::Clean) => clean(),
+    }
+}
+
+fn clean() {
+    let config = Config::load(Path::new("."), None).unwrap_or_default();
+    let dir = config.target_dir();
+
+    if let Err(error) = target::clean(&dir) {
+        error!(%error, dir = %dir.display(), "failed to clean target dir");
+        std::process::exit(1);
+    }
+
+    info!(dir = %dir.display(), "cleaned target dir");
+}
+
+fn report(args: cli::Report) {
+    let root = project_root();
+    let config = project_config(&root, &args.input);
+    let coverage = load_coverage(&root, &config, &config.target_dir());
+
+    let ignore = Ignore::project(&root);
+    let files = summary::files(&root, &coverage, &ignore, &args.files);
+
+    let color = args.color.enabled();
+    let mut out = std::io::stdout().lock();
+
+    let result = files
+        .iter()
+        .filter(|_| !args.summary)
+        .try_for_each(|file| {
+            annotate::listing(&mut out, &file.path, &file.content, &file.lines, color)
+        })
+        .and_then(|_| {
+            let totals = files
+                .iter()
+                .map(|file| {
+                    (
+                        file.path.as_path(),
+                        summary::Totals::of(file.lines.values()),
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            annotate::summary(&mut out, &totals)
+        });
+
+    finish_output(result);
+}
+
+fn check(args: cli::Check) {
+    let root = project_root();
+    let mut config = project_config(&root, &args.input);
+    config.check.total = args.total.or(config.check.total);
+    config.check.file = args.file.or(config.check.file);
+    config.check.function = args.function.or(config.check.function);
+
+    // the lock is held until the coverage written by the run has been read
+    let target = if args.run {
+        let target = match TargetDir::acquire(&config.target_dir()) {
+            Ok(target) => target,
+            Err(error) => {
+                error!(%error, "failed to set up target dir");
+                std::process::exit(2);
+            }
+        };
+
+        if let Err(error) = check::run(&config.runner, target.path()) {
+            error!(%error, "coverage run failed");
+            std::process::exit(2);
+        }
+
+        Some(target)
+    } else {
+        None
+    };
+
+    let target_dir = match &target {
+        Some(target) => target.path().to_path_buf(),
+        None => config.target_dir(),
+    };
+
+    let coverage = load_coverage(&root, &config, &target_dir);
+    drop(target);
+
+    let ignore = Ignore::project(&root);
+    let files = summary::files(&root, &coverage, &ignore, &[]);
+    let checks = check::evaluate(&files, &config.check);
+
+    let mut out = std::io::stdout().lock();
+    let result = match args.output {
+        cli::Output::Human => check::human(&mut out, &files, &checks),
+        cli::Output::Github => check::github(&mut out, &files, &checks),
+        cli::Output::Sarif => check::sarif(&mut out, &files, &checks),
+        cli::Output::Junit => check::junit(&mut out, &checks),
+    };
+
+    finish_output(result);
+
+    if checks.iter().any(|check| !check.passed()) {
+        std::process::exit(1);
+    }
+}
 
-            Connection::stdio()
+fn project_root() -> PathBuf {
+    match std::env::current_dir() {
+        Ok(root) => root,
+        Err(error) => {
+            error!(%error, "failed to determine project root");
+            std::process::exit(2);
+        }
+    }
+}
+
+/// the project configuration with the coverage file given on the command line
+fn project_config(root: &Path, input: &cli::Input) -> Config {
+    let mut config = match Config::load(root, None) {
+        Ok(config) => config,
+        Err(error) => {
+            error!(%error, "failed to load configuration, using defaults");
+            Config::default()
         }
     };
 
-    let (id, params) = conn.initialize_start().unwrap();
+    if let Some(path) = &input.coverage {
+        config.coverage.path = Some(path.clone());
+    }
+    if let Some(format) = input.format {
+        config.coverage.format = format;
+    }
+
+    config
+}
+
+fn load_coverage(root: &Path, config: &Config, target_dir: &Path) -> Coverage {
+    let pkg = cargo_toml::Manifest::from_path(root.join("Cargo.toml"))
+        .ok()
+        .and_then(|manifest| manifest.package.map(|package| package.name))
+        .unwrap_or_default();
+
+    let source = config.source(pkg, target_dir, root);
+    match source.load() {
+        Ok(coverage) => coverage,
+        Err(error) => {
+            error!(%error, path = %source.path().display(), "failed to load coverage");
+            std::process::exit(2);
+        }
+    }
+}
+
+fn finish_output(result: std::io::Result<()>) {
+    // a closed pipe means whoever reads the output has seen enough
+    if let Err(error) = result.or_else(|error| match error.kind() {
+        std::io::ErrorKind::BrokenPipe => Ok(()),
+        _ => Err(error),
+    }) {
+        error!(%error, "failed to write output");
+        std::process::exit(2);
+    }
+}
+
+fn serve(args: cli::Serve) {
+    info!(connect = %args.connect, "planning on connecting");
+
+    let (conn, threads) = match args.connect.open() {
+        Ok(pair) => pair,
+        Err(error) => {
+            error!(%error, "failed to connect to editor");
+            return;
+        }
+    };
+
+    let (id, params) = match conn.initialize_start() {
+        Ok(init) => init,
+        Err(error) => {
+            error!(%error, "failed to start initialization");
+            return;
+        }
+    };
 
     debug!(%params, "initialization params");
 
@@ -65,22 +257,24 @@ fn main() {
 
     });
 
-    let mut ignore = Ignore::default();
-    let mut check = false;
-    if let Ok(project) = Ignore::load(&PathBuf::from("tarballin-ignore")) {
-        ignore += project;
-        check = true;
-    }
-    if let Ok(project) = Ignore::load(&PathBuf::from(".tarballin-ignore")) {
-        ignore += project;
-        check = true;
-    }
+    let ignore = Ignore::project(Path::new("."));
+
+    debug!(?ignore, "ignore file");
 
-    if check && ignore.is_empty() {
-        // should turn off language server
+    let mut config = match Config::load(Path::new("."), init.initialization_options.as_ref()) {
+        Ok(config) => config,
+        Err(error) => {
+            error!(%error, "failed to load configuration, using defaults");
+            Config::default()
+        }
+    };
+
+    if let Some(path) = args.coverage {
+        config.coverage.path = Some(path);
+        config.coverage.offline = true;
     }
 
-    debug!(?ignore, "ignore file");
+    debug!(?config, "configuration");
 
     let pkg = {
         let manifest = cargo_toml::Manifest::from_path("Cargo.toml").unwrap();
@@ -94,21 +288,92 @@ fn main() {
         .map(|ws| ws.uri.to_file_path().unwrap())
         .collect::<Vec<_>>();
 
-    debug!(?initialize_data, "finished initialization");
+    // held until the end of the session so no other instance builds in it, nothing is
+    // built when the coverage is read from a file
+    let target = if config.coverage.offline {
+        None
+    } else {
+        match TargetDir::acquire(&config.target_dir()) {
+            Ok(target) => Some(target),
+            Err(error) => {
+                error!(%error, "failed to set up target dir");
+
+                let message = format!("failed to set up target dir: {error}");
+                let response = Response::new_err(id, ErrorCode::InternalError as i32, message);
+                if conn.sender.send(Message::Response(response)).is_ok() {
+                    // the editor ends the session once initialization failed
+                    drop(conn);
+                    let _ = threads.join();
+                }
+                return;
+            }
+        }
+    };
+    let target_dir = match &target {
+        Some(target) => target.path().to_path_buf(),
+        None => config.target_dir(),
+    };
 
-    conn.initialize_finish(id, initialize_data).unwrap();
+    debug!(?initialize_data, "finished initialization");
 
-    let tmpdir = tempdir::TempDir::new("tarballin").unwrap();
-    let target_dir = tmpdir.as_ref().to_path_buf();
+    if let Err(error) = conn.initialize_finish(id, initialize_data) {
+        error!(%error, "failed to finish initialization");
+        return;
+    }
 
     let (trigger_tx, trigger_rx) = bounded(8);
     let (report_tx, report_rx) = bounded(8);
 
-    let ingest_handle = std::thread::spawn(move || workers::ingest(conn.receiver, trigger_tx));
-    let process_handle = std::thread::spawn(move || {
-        workers::process(pkg, target_dir, workspaces, ignore, trigger_rx, report_tx)
+    let root = match std::env::current_dir() {
+        Ok(root) => root,
+        Err(error) => {
+            error!(%error, "failed to determine project root");
+            return;
+        }
+    };
+
+    // a report at a configured path would be shared with the main runs
+    let indexer = (config.index.enabled
+        && !config.coverage.offline
+        && config.coverage.path.is_none())
+    .then(|| {
+        let target = target_dir.join("index");
+
+        index::Indexer {
+            runner: config.runner.clone(),
+            source: config.source(pkg.clone(), &target, &root),
+            target,
+            root: root.clone(),
+        }
     });
-    let report_handle = std::thread::spawn(move || workers::report(report_rx, conn.sender));
+
+    // a report at a configured path would be overwritten by single test runs too
+    let test_source = config
+        .coverage
+        .path
+        .is_none()
+        .then(|| config.source(pkg.clone(), &runner::test_target(&target_dir), &root));
+    let source = config.source(pkg, &target_dir, &root);
+
+    let diagnostics = config.diagnostics.clone();
+    let hints = config.hints.clone();
+
+    let project = workers::Project {
+        source,
+        test_source,
+        root,
+        target: target_dir,
+        workspaces,
+        ignore,
+        indexer,
+        config,
+    };
+
+    let ingest_handle = std::thread::spawn(move || workers::ingest(conn.receiver, trigger_tx));
+    let process_handle =
+        std::thread::spawn(move || workers::process(project, mode, trigger_rx, report_tx));
+    let report_handle =
+        std::thread::spawn(move || workers::report(report_rx, conn.sender, diagnostics, hints));
 
     trace!("joining process");
     process_handle.join().unwrap();
@@ -120,5 +385,8 @@ fn main() {
     ingest_handle.join().unwrap();
 
     trace!("joining lsp io threads");
-    threads.join().unwrap();
+    if let Err(error) = threads.join() {
+        // the editor hanging up on a socket is a normal way to end a session
+        debug!(%error, "lsp io threads finished with error");
+    }
 }
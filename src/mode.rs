@@ -1,10 +1,14 @@
 use lsp_types::{
-    DiagnosticOptions, DiagnosticServerCapabilities, InitializeParams, SaveOptions,
+    CodeActionProviderCapability, CodeLensOptions, DiagnosticOptions, DiagnosticServerCapabilities,
+    ExecuteCommandOptions, HoverProviderCapability, InitializeParams, OneOf, SaveOptions,
+    SemanticTokensFullOptions, SemanticTokensOptions, SemanticTokensServerCapabilities,
     ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncOptions,
     WorkDoneProgressOptions,
 };
 use tracing::error;
 
+use crate::{heatmap, lsp_ext};
+
 #[derive(Debug, Clone, Copy)]
 pub enum Mode {
     Workspace,
@@ -56,7 +60,7 @@ impl TryFrom<&InitializeParams> for Mode {
 
 impl Mode {
     pub fn capabilities(&self) -> ServerCapabilities {
-        match self {
+        let capabilities = match self {
             Mode::Workspace => ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Options(
                     TextDocumentSyncOptions {
@@ -130,6 +134,34 @@ impl Mode {
 
                 ..ServerCapabilities::default()
             },
+        };
+
+        // features that only answer requests work the same in every mode
+        ServerCapabilities {
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            inlay_hint_provider: Some(OneOf::Left(true)),
+            code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+            code_lens_provider: Some(CodeLensOptions {
+                resolve_provider: Some(false),
+            }),
+            execute_command_provider: Some(ExecuteCommandOptions {
+                commands: vec![
+                    lsp_ext::RUN.to_string(),
+                    lsp_ext::RUN_TEST.to_string(),
+                    lsp_ext::SHOW_ALL.to_string(),
+                    lsp_ext::ADD_IGNORE.to_string(),
+                ],
+                work_done_progress_options: WorkDoneProgressOptions::default(),
+            }),
+            semantic_tokens_provider: Some(
+                SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                    legend: heatmap::legend(),
+                    range: Some(true),
+                    full: Some(SemanticTokensFullOptions::Bool(true)),
+                }),
+            ),
+            ..capabilities
         }
     }
 }
@@ -1,11 +1,19 @@
 use std::{
+    io::{self, Read},
+    os::unix::process::CommandExt,
     path::{Path, PathBuf},
-    process::{Child, Command, Stdio},
-    time::Duration,
+    process::{Child, Command, ExitStatus, Stdio},
+    thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
 use crossbeam_channel::{select, Receiver, Sender};
-use tracing::{error, trace};
+use tracing::{debug, error, trace, warn};
+
+use crate::config::RunnerConfig;
+
+/// how long tarpaulin gets to wind down before it is killed outright
+const GRACE: Duration = Duration::from_secs(5);
 
 #[derive(Debug, thiserror::Error)]
 pub enum RunError {
@@ -15,51 +23,99 @@ pub enum RunError {
 
 #[derive(PartialEq, Eq)]
 pub enum Input {
-    Run,
+    /// a run of every test, or of those matching the filter
+    Run(Option<Filter>),
     Exit,
 }
 
+/// the tests a run is limited to
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Filter {
+    pub name: String,
+    /// only the test with exactly this name, instead of every test whose name contains it
+    pub exact: bool,
+}
+
+/// `Starting` and `Success` carry the test filter of the run
 pub enum Status {
-    Success,
-    Failure,
+    Success(Option<String>),
+    Failure(Output),
     Reset,
-    Starting,
+    Starting(Option<String>),
+}
+
+/// everything a finished run printed
+#[derive(Default, Debug)]
+pub struct Output {
+    pub stdout: String,
+    pub stderr: String,
 }
 
-pub fn runner_thread(target_dir: PathBuf, input: Receiver<Input>, status: Sender<Status>) {
+impl Status {
+    /// a run that never got going, reported like one that failed
+    fn failed(error: impl std::fmt::Display) -> Status {
+        Status::Failure(Output {
+            stderr: error.to_string(),
+            ..Output::default()
+        })
+    }
+}
+
+fn name(filter: &Option<Filter>) -> Option<String> {
+    filter.as_ref().map(|filter| filter.name.clone())
+}
+
+/// where runs limited to some tests build and report, away from the full runs
+pub fn test_target(target_dir: &Path) -> PathBuf {
+    target_dir.join("test")
+}
+
+pub fn runner_thread(
+    target_dir: PathBuf,
+    config: RunnerConfig,
+    input: Receiver<Input>,
+    status: Sender<Status>,
+) {
     loop {
-        let Ok(w) = input.recv() else {
-            return;
+        let mut filter = match input.recv() {
+            Ok(Input::Run(filter)) => filter,
+            Ok(Input::Exit) | Err(_) => return,
         };
 
-        if w != Input::Run {
-            continue;
-        }
-
-        if status.send(Status::Starting).is_err() {
+        if status.send(Status::Starting(name(&filter))).is_err() {
             return;
         }
 
-        let mut child = match run(&target_dir) {
-            Ok(child) => child,
+        let mut job = match Job::spawn(&config, &target_dir, filter.as_ref()) {
+            Ok(job) => job,
             Err(error) => {
                 error!(%error, "failed to run command");
+                if status.send(Status::failed(error)).is_err() {
+                    return;
+                }
                 continue;
             }
         };
 
         'check: loop {
-            let Ok(job_st) = child.try_wait() else {
-                break 'check;
+            let job_st = match job.try_wait() {
+                Ok(job_st) => job_st,
+                Err(error) => {
+                    error!(%error, "failed to check on tarpaulin");
+                    if status.send(Status::failed(error)).is_err() {
+                        return;
+                    }
+                    break 'check;
+                }
             };
 
             if let Some(st) = job_st {
                 trace!(%st, "job completed");
                 if st.success() {
-                    if status.send(Status::Success).is_err() {
+                    if status.send(Status::Success(name(&filter))).is_err() {
                         return;
                     }
-                } else if status.send(Status::Failure).is_err() {
+                } else if status.send(Status::Failure(job.output())).is_err() {
                     return;
                 }
 
@@ -68,6 +124,7 @@ pub fn runner_thread(target_dir: PathBuf, input: Receiver<Input>, status: Sender
 
             let i = select! {
                 recv(input) -> i => {
+                    // dropping the job takes tarpaulin down with us
                     let Ok(i) = i else { return; };
                     i
                 }
@@ -80,34 +137,223 @@ pub fn runner_thread(target_dir: PathBuf, input: Receiver<Input>, status: Sender
             match i {
                 Input::Exit => return,
 
-                Input::Run => {
+                Input::Run(next) => {
                     if status.send(Status::Reset).is_err() {
                         return;
                     }
 
-                    child = match run(&target_dir) {
-                        Ok(child) => child,
+                    job.kill();
+                    filter = next;
+
+                    job = match Job::spawn(&config, &target_dir, filter.as_ref()) {
+                        Ok(job) => job,
                         Err(error) => {
                             error!(%error, "failed to run command");
-                            continue;
+                            if status.send(Status::failed(error)).is_err() {
+                                return;
+                            }
+                            break 'check;
                         }
                     };
+
+                    if status.send(Status::Starting(name(&filter))).is_err() {
+                        return;
+                    }
                 }
             }
         }
     }
 }
 
-fn run(path: &Path) -> Result<Child, RunError> {
-    trace!("spawning tarpaulin");
-    let proc = Command::new("cargo")
-        .arg("tarpaulin")
-        .arg("--target-dir")
-        .arg(path)
-        .stdin(Stdio::null())
-        .stderr(Stdio::null())
-        .stdout(Stdio::null())
-        .spawn()?;
-
-    Ok(proc)
+/// a tarpaulin invocation running in its own process group
+///
+/// cargo, rustc and the test binaries all share the group, so the whole tree
+/// can be taken down at once. The group is killed and reaped on drop.
+pub struct Job {
+    child: Child,
+    reaped: bool,
+    stdout: Option<JoinHandle<String>>,
+    stderr: Option<JoinHandle<String>>,
+}
+
+impl Job {
+    fn spawn(config: &RunnerConfig, path: &Path, filter: Option<&Filter>) -> Result<Job, RunError> {
+        // compiler messages come as json so build errors can be put on the code
+        let mut cmd = match filter {
+            Some(filter) => config.command(&test_target(path), Some(&filter.name), true),
+            None => config.command(path, None, true),
+        };
+
+        if filter.is_some_and(|filter| filter.exact) {
+            cmd.arg("--exact");
+        }
+
+        Job::start(cmd)
+    }
+
+    /// runs a command in a process group of its own, with its output captured
+    pub fn start(mut cmd: Command) -> Result<Job, RunError> {
+        trace!(?cmd, "spawning tarpaulin");
+
+        let mut child = cmd
+            .process_group(0)
+            .stdin(Stdio::null())
+            .stderr(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        // drained continuously so a chatty build can never block on a full pipe
+        let stdout = child.stdout.take().map(capture);
+        let stderr = child.stderr.take().map(capture);
+
+        Ok(Job {
+            child,
+            reaped: false,
+            stdout,
+            stderr,
+        })
+    }
+
+    /// collects what the job printed, only meaningful once it has exited
+    pub fn output(&mut self) -> Output {
+        let collect = |handle: Option<JoinHandle<String>>| {
+            handle
+                .and_then(|handle| handle.join().ok())
+                .unwrap_or_default()
+        };
+
+        Output {
+            stdout: collect(self.stdout.take()),
+            stderr: collect(self.stderr.take()),
+        }
+    }
+
+    /// the exit status once tarpaulin is done, anything it left behind in the group is killed first
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        if self.reaped {
+            return self.child.try_wait();
+        }
+
+        if !self.exited()? {
+            return Ok(None);
+        }
+
+        self.reap().map(Some)
+    }
+
+    /// terminates the process group, escalating to SIGKILL after the grace period
+    fn kill(&mut self) {
+        if self.reaped {
+            return;
+        }
+
+        debug!(pgid = self.pgid(), "terminating tarpaulin process group");
+        signal_group(self.pgid(), libc::SIGTERM);
+
+        let deadline = Instant::now() + GRACE;
+        while Instant::now() < deadline {
+            match self.exited() {
+                Ok(false) => std::thread::sleep(Duration::from_millis(50)),
+                _ => break,
+            }
+        }
+
+        if let Err(error) = self.reap() {
+            warn!(%error, "failed to reap tarpaulin");
+        }
+    }
+
+    /// the child was spawned as the group leader, so its pid is the group id
+    fn pgid(&self) -> libc::pid_t {
+        self.child.id() as libc::pid_t
+    }
+
+    /// whether the leader has exited, without reaping it so the group id stays ours
+    fn exited(&self) -> io::Result<bool> {
+        // SAFETY: an all zero siginfo_t is valid, waitid only writes into it
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+
+        // SAFETY: `info` outlives the call, WNOWAIT leaves the child waitable
+        let res = unsafe {
+            libc::waitid(
+                libc::P_PID,
+                self.pgid() as libc::id_t,
+                &mut info,
+                libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+            )
+        };
+
+        if res != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: waitid succeeded, a pid of zero means the child is still running
+        Ok(unsafe { info.si_pid() } != 0)
+    }
+
+    /// kills whatever is left in the group, stragglers that outlived cargo included,
+    /// then reaps the leader, after which the group id may belong to someone else
+    fn reap(&mut self) -> io::Result<ExitStatus> {
+        signal_group(self.pgid(), libc::SIGKILL);
+
+        let status = self.child.wait();
+        self.reaped = true;
+        status
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+fn capture(mut pipe: impl Read + Send + 'static) -> JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Err(error) = pipe.read_to_end(&mut buf) {
+            warn!(%error, "failed to read tarpaulin output");
+        }
+
+        String::from_utf8_lossy(&buf).into_owned()
+    })
+}
+
+fn signal_group(pgid: libc::pid_t, signal: libc::c_int) {
+    // SAFETY: kill has no memory safety requirements, a negative pid addresses the group
+    let res = unsafe { libc::kill(-pgid, signal) };
+
+    if res != 0 {
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() != Some(libc::ESRCH) {
+            warn!(%error, pgid, signal, "failed to signal process group");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reaped() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "sleep 30 & echo started"]);
+
+        let mut job = Job::start(cmd).unwrap();
+        let deadline = Instant::now() + GRACE;
+        let status = loop {
+            if let Some(status) = job.try_wait().unwrap() {
+                break status;
+            }
+
+            assert!(Instant::now() < deadline, "job never exited");
+            std::thread::sleep(Duration::from_millis(20));
+        };
+
+        assert!(status.success());
+        assert!(job.reaped);
+        // the straggler kept the pipe open, it only closes once the group is gone
+        assert_eq!(job.output().stdout, "started\n");
+    }
 }
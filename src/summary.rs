@@ -0,0 +1,246 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use tracing::error;
+
+use crate::{
+    coverage::{Coverage, Stats, Trace},
+    ignore::Ignore,
+    syntax::{self, Function},
+};
+
+/// a source file from the coverage, with the ignore rules applied
+pub struct File {
+    /// relative to the project root where possible
+    pub path: PathBuf,
+    pub content: Vec<u8>,
+    pub lines: BTreeMap<u32, Line>,
+}
+
+/// what the traces on one source line add up to
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Line {
+    /// `None` when the line only carries branch data
+    pub hits: Option<usize>,
+    pub covered: bool,
+    pub untaken: Vec<String>,
+    /// the function name the coverage tool gave, if any
+    pub function: Option<String>,
+}
+
+/// a function with the lines traced inside it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionCoverage {
+    pub function: Function,
+    pub totals: Totals,
+    /// the name the coverage tool reported for it, often mangled or module qualified
+    pub reported: Option<String>,
+}
+
+/// covered out of total lines
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Totals {
+    pub covered: usize,
+    pub total: usize,
+}
+
+/// folds traces into their lines, a line counts as covered when any trace on it is
+pub fn lines(traces: &[Trace]) -> BTreeMap<u32, Line> {
+    let mut lines = BTreeMap::<u32, Line>::new();
+
+    for trace in traces {
+        let line = lines.entry(trace.line).or_default();
+
+        if let Stats::Line(hits) = trace.stats {
+            line.hits = Some(line.hits.unwrap_or_default().max(hits));
+        }
+
+        line.covered |= trace.stats.covered();
+        line.untaken.extend(trace.stats.untaken());
+
+        if line.function.is_none() {
+            line.function.clone_from(&trace.fn_name);
+        }
+    }
+
+    lines
+}
+
+impl Totals {
+    pub fn of<'a>(lines: impl IntoIterator<Item = &'a Line>) -> Self {
+        let mut totals = Totals::default();
+
+        for line in lines {
+            totals.covered += line.covered as usize;
+            totals.total += 1;
+        }
+
+        totals
+    }
+
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            return 100.0;
+        }
+
+        self.covered as f64 * 100.0 / self.total as f64
+    }
+}
+
+impl std::ops::AddAssign for Totals {
+    fn add_assign(&mut self, rhs: Self) {
+        self.covered += rhs.covered;
+        self.total += rhs.total;
+    }
+}
+
+/// every file with coverage left after ignoring, sorted by path
+///
+/// `only` narrows the files down to those ending in one of the given paths
+pub fn files(root: &Path, coverage: &Coverage, ignore: &Ignore, only: &[PathBuf]) -> Vec<File> {
+    let mut files = Vec::new();
+
+    for (path, traces) in &coverage.traces {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        if !only.is_empty() && !only.iter().any(|file| relative.ends_with(file)) {
+            continue;
+        }
+
+        let content = match std::fs::read(path) {
+            Ok(content) => content,
+            Err(error) => {
+                error!(%error, path = %path.display(), "failed to read source");
+                continue;
+            }
+        };
+
+        let traces = match ignore.matches(relative).filter(&content, traces) {
+            Ok(traces) => traces,
+            Err(error) => {
+                error!(%error, path = %path.display(), "failed to apply ignore rules");
+                continue;
+            }
+        };
+
+        if traces.is_empty() {
+            continue;
+        }
+
+        files.push(File {
+            path: relative.to_path_buf(),
+            content,
+            lines: lines(&traces),
+        });
+    }
+
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    files
+}
+
+/// runs of uncovered lines as first and last line, only untraced lines may sit in between
+pub fn uncovered(lines: &BTreeMap<u32, Line>) -> Vec<(u32, u32)> {
+    let mut runs = Vec::<(u32, u32)>::new();
+    let mut open = false;
+
+    for (number, line) in lines {
+        match runs.last_mut() {
+            Some((_, last)) if open && !line.covered => *last = *number,
+            _ if !line.covered => runs.push((*number, *number)),
+            _ => (),
+        }
+
+        open = !line.covered;
+    }
+
+    runs
+}
+
+/// the coverage of every function outside of tests that has traced lines
+///
+/// lines of nested functions count towards the enclosing one as well
+pub fn functions(
+    content: &[u8],
+    lines: &BTreeMap<u32, Line>,
+) -> eyre::Result<Vec<FunctionCoverage>> {
+    let tree = syntax::parse(content)?;
+
+    let functions = syntax::functions(&tree, content)
+        .into_iter()
+        .filter(|function| !function.test)
+        .filter_map(|function| {
+            let first = function.start.row as u32 + 1;
+            let last = function.end.row as u32 + 1;
+            let traced = lines.range(first..=last).map(|(_, line)| line);
+            let totals = Totals::of(traced.clone());
+            let reported = traced.filter_map(|line| line.function.clone()).next();
+
+            (totals.total > 0).then_some(FunctionCoverage {
+                function,
+                totals,
+                reported,
+            })
+        })
+        .collect();
+
+    Ok(functions)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_functions() {
+        const CONTENT: &[u8] =
+            b"fn a() {\n    b();\n}\n\nfn b() {}\n\nfn c() {\n    todo!()\n}\n\n#[test]\nfn d() {\n    a();\n}\n";
+
+        let mut traces = vec![
+            Trace::new(1, Stats::Line(1)),
+            Trace::new(2, Stats::Line(1)),
+            Trace::new(5, Stats::Line(2)),
+            Trace::new(7, Stats::Line(0)),
+            Trace::new(8, Stats::Line(0)),
+            Trace::new(12, Stats::Line(1)),
+        ];
+        traces[2].fn_name = Some("demo::b".to_string());
+
+        let functions = functions(CONTENT, &lines(&traces)).unwrap();
+        let summary = functions
+            .iter()
+            .map(|f| {
+                (
+                    f.function.name.as_str(),
+                    f.totals.covered,
+                    f.totals.total,
+                    f.reported.as_deref(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("a", 2, 2, None),
+                ("b", 1, 1, Some("demo::b")),
+                ("c", 0, 2, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_uncovered() {
+        let traces = vec![
+            Trace::new(1, Stats::Line(0)),
+            Trace::new(2, Stats::Line(1)),
+            Trace::new(3, Stats::Line(0)),
+            Trace::new(5, Stats::Line(0)),
+            Trace::new(6, Stats::Line(1)),
+            Trace::new(9, Stats::Line(0)),
+        ];
+
+        assert_eq!(uncovered(&lines(&traces)), vec![(1, 1), (3, 5), (9, 9)]);
+    }
+}
@@ -0,0 +1,424 @@
+use std::path::Path;
+
+use eyre::ContextCompat;
+use tree_sitter::{Node, Parser, Point, Tree};
+use tree_sitter_rust::language;
+
+/// a `fn` item found in a source file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    /// inline modules the function is nested in, outermost first
+    pub modules: Vec<String>,
+    /// the type of the `impl` or the trait the function is declared in
+    pub owner: Option<String>,
+    pub start: Point,
+    pub end: Point,
+    pub name_start: Point,
+    pub name_end: Point,
+    /// whether the function carries a `#[test]` like attribute
+    pub test: bool,
+}
+
+pub fn parse(content: &[u8]) -> eyre::Result<Tree> {
+    let mut parser = Parser::new();
+    parser.set_language(language())?;
+
+    parser
+        .parse(content, None)
+        .with_context(|| "failed to parse tree")
+}
+
+/// every function item in the tree, in source order
+pub fn functions(tree: &Tree, content: &[u8]) -> Vec<Function> {
+    let mut functions = Vec::new();
+    let mut scope = Scope::default();
+
+    collect(tree.root_node(), content, &mut scope, &mut functions);
+
+    functions
+}
+
+/// the module a source file is within its crate, outermost first, empty for a crate root
+///
+/// the crate is taken from the closest `Cargo.toml` above the file, `#[path]` attributes aren't followed
+pub fn module_path(path: &Path) -> Vec<String> {
+    let Some(manifest) = path
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.join("Cargo.toml").is_file())
+    else {
+        return Vec::new();
+    };
+    let Ok(relative) = path
+        .with_extension("")
+        .strip_prefix(manifest)
+        .map(Path::to_path_buf)
+    else {
+        return Vec::new();
+    };
+
+    let mut parts = relative
+        .iter()
+        .map(|part| part.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+
+    // `src` holds a single crate, the other directories a crate per file or directory
+    let root = match parts.first().map(String::as_str) {
+        Some("src") if parts.len() > 2 && parts[1] == "bin" => 3,
+        Some("src") => 1,
+        Some("tests" | "benches" | "examples") => 2,
+        _ => return Vec::new(),
+    };
+
+    let mut modules = parts.split_off(root.min(parts.len()));
+    if modules.last().is_some_and(|module| module == "mod") {
+        modules.pop();
+    }
+
+    if let [file] = modules.as_slice() {
+        if file == "lib" || file == "main" {
+            modules.clear();
+        }
+    }
+
+    modules
+}
+
+impl Function {
+    /// the name with the `impl` type in front, like `Parser::parse`
+    pub fn qualified_name(&self) -> String {
+        match &self.owner {
+            Some(owner) => format!("{owner}::{}", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// where in the tree the collection currently is
+#[derive(Default)]
+struct Scope {
+    modules: Vec<String>,
+    owner: Option<String>,
+}
+
+fn collect(node: Node, content: &[u8], scope: &mut Scope, out: &mut Vec<Function>) {
+    let mut cursor = node.walk();
+
+    for child in node.named_children(&mut cursor) {
+        match child.kind() {
+            "function_item" => {
+                if let Some(name) = child.child_by_field_name("name") {
+                    out.push(Function {
+                        name: text(name, content),
+                        modules: scope.modules.clone(),
+                        owner: scope.owner.clone(),
+                        start: child.start_position(),
+                        end: child.end_position(),
+                        name_start: name.start_position(),
+                        name_end: name.end_position(),
+                        test: is_test(child, content),
+                    });
+                }
+
+                if let Some(body) = child.child_by_field_name("body") {
+                    // items inside a function body don't belong to its impl
+                    let owner = scope.owner.take();
+                    collect(body, content, scope, out);
+                    scope.owner = owner;
+                }
+            }
+
+            "impl_item" | "trait_item" => {
+                let Some(body) = child.child_by_field_name("body") else {
+                    continue;
+                };
+
+                let field = if child.kind() == "impl_item" {
+                    "type"
+                } else {
+                    "name"
+                };
+                let owner = child.child_by_field_name(field).map(|ty| {
+                    let ty = text(ty, content);
+                    ty.split('<').next().unwrap_or_default().trim().to_string()
+                });
+
+                let outer = std::mem::replace(&mut scope.owner, owner);
+                collect(body, content, scope, out);
+                scope.owner = outer;
+            }
+
+            "mod_item" => {
+                let Some(body) = child.child_by_field_name("body") else {
+                    continue;
+                };
+
+                let name = child
+                    .child_by_field_name("name")
+                    .map(|name| text(name, content))
+                    .unwrap_or_default();
+
+                let owner = scope.owner.take();
+                scope.modules.push(name);
+                collect(body, content, scope, out);
+                scope.modules.pop();
+                scope.owner = owner;
+            }
+
+            _ => collect(child, content, scope, out),
+        }
+    }
+}
+
+/// an attribute line to put in front of an item
+#[derive(Debug, PartialEq)]
+pub struct Insertion {
+    pub title: String,
+    /// the start of the item's first line, below any attributes and doc comments it has
+    pub at: Point,
+    /// the attribute with the item's indentation and a newline
+    pub text: String,
+}
+
+/// puts `attribute` on the innermost function, `impl` block and module around `point`
+///
+/// items that already carry the attribute are left alone
+pub fn attribute_insertions(
+    content: &[u8],
+    point: Point,
+    attribute: &str,
+) -> eyre::Result<Vec<Insertion>> {
+    let tree = parse(content)?;
+    let squash = |text: &str| text.split_whitespace().collect::<String>();
+    let wanted = squash(attribute);
+
+    let mut insertions = Vec::new();
+    let mut seen = Vec::new();
+
+    let mut node = tree.root_node().descendant_for_point_range(point, point);
+    while let Some(item) = node {
+        node = item.parent();
+
+        let title = match item.kind() {
+            "function_item" => item
+                .child_by_field_name("name")
+                .map(|name| format!("Exclude fn `{}` from coverage", text(name, content))),
+            "impl_item" => item
+                .child_by_field_name("type")
+                .map(|ty| format!("Exclude impl `{}` from coverage", text(ty, content))),
+            "mod_item" => item
+                .child_by_field_name("name")
+                .map(|name| format!("Exclude mod `{}` from coverage", text(name, content))),
+            _ => continue,
+        };
+
+        let Some(title) = title else { continue };
+        if seen.contains(&item.kind()) {
+            continue;
+        }
+        seen.push(item.kind());
+
+        if attributes(item, content).any(|attr| squash(&attr) == wanted) {
+            continue;
+        }
+
+        let row = item.start_position().row;
+        let line = content.split(|b| *b == b'\n').nth(row).unwrap_or_default();
+        let indent = line
+            .iter()
+            .take_while(|b| matches!(b, b' ' | b'\t'))
+            .map(|b| *b as char)
+            .collect::<String>();
+
+        insertions.push(Insertion {
+            title,
+            at: Point::new(row, 0),
+            text: format!("{indent}{}\n", attribute.trim()),
+        });
+    }
+
+    Ok(insertions)
+}
+
+/// the outer attributes written above an item, through any comments between them
+fn attributes<'a>(item: Node<'a>, content: &'a [u8]) -> impl Iterator<Item = String> + 'a {
+    let mut prev = item.prev_named_sibling();
+
+    std::iter::from_fn(move || loop {
+        let node = prev?;
+        prev = node.prev_named_sibling();
+
+        match node.kind() {
+            "attribute_item" => return Some(text(node, content)),
+            "line_comment" | "block_comment" => (),
+            _ => return None,
+        }
+    })
+}
+
+/// looks through the attributes directly above an item for `#[test]`, `#[tokio::test]` and friends
+fn is_test(item: Node, content: &[u8]) -> bool {
+    attributes(item, content).any(|attr| {
+        let attr = attr
+            .trim_start_matches("#[")
+            .trim_end_matches(']')
+            .split('(')
+            .next()
+            .unwrap_or_default()
+            .trim();
+
+        attr == "test" || attr.ends_with("::test")
+    })
+}
+
+fn text(node: Node, content: &[u8]) -> String {
+    node.utf8_text(content).unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_functions() {
+        const CONTENT: &str = r#"
+fn main() {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_main() {
+        main();
+    }
+
+    /// not a test
+    #[allow(dead_code)]
+    fn helper() {}
+
+    #[tokio::test(flavor = "multi_thread")]
+    // async
+    async fn test_async() {}
+}
+
+impl<T> Parser<T> {
+    fn parse(&self) {
+        fn inner() {}
+    }
+}
+"#;
+
+        let tree = parse(CONTENT.as_bytes()).unwrap();
+        let functions = functions(&tree, CONTENT.as_bytes());
+
+        let summary = functions
+            .iter()
+            .map(|f| {
+                (
+                    f.qualified_name(),
+                    f.modules.join("::"),
+                    f.test,
+                    f.start.row,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let expected = vec![
+            ("main", "", false, 1),
+            ("test_main", "test", true, 8),
+            ("helper", "test", false, 14),
+            ("test_async", "test", true, 18),
+            ("Parser::parse", "", false, 22),
+            ("inner", "", false, 23),
+        ];
+
+        assert_eq!(
+            summary,
+            expected
+                .into_iter()
+                .map(|(name, modules, test, row)| (
+                    name.to_string(),
+                    modules.to_string(),
+                    test,
+                    row
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_attribute_insertions() {
+        const ATTRIBUTE: &str = "#[cfg_attr(coverage_nightly, coverage(off))]";
+        const CONTENT: &str = r#"mod parser {
+    impl Parser {
+        /// parses
+        #[inline]
+        pub fn parse(&self) {
+            todo!()
+        }
+
+        #[cfg_attr( coverage_nightly, coverage(off) )]
+        fn skip(&self) {
+            todo!()
+        }
+    }
+}
+"#;
+
+        let insertions = attribute_insertions(CONTENT.as_bytes(), Point::new(5, 12), ATTRIBUTE)
+            .unwrap()
+            .into_iter()
+            .map(|i| (i.title, i.at.row, i.text))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            insertions,
+            vec![
+                (
+                    "Exclude fn `parse` from coverage".to_string(),
+                    4,
+                    format!("        {ATTRIBUTE}\n")
+                ),
+                (
+                    "Exclude impl `Parser` from coverage".to_string(),
+                    1,
+                    format!("    {ATTRIBUTE}\n")
+                ),
+                (
+                    "Exclude mod `parser` from coverage".to_string(),
+                    0,
+                    format!("{ATTRIBUTE}\n")
+                ),
+            ]
+        );
+
+        let skipped =
+            attribute_insertions(CONTENT.as_bytes(), Point::new(10, 12), ATTRIBUTE).unwrap();
+        assert_eq!(skipped.len(), 2);
+        assert!(skipped.iter().all(|i| !i.title.contains("skip")));
+    }
+
+    #[test]
+    fn test_module_path() {
+        let root = tempdir::TempDir::new("tarballin-syntax").unwrap();
+        std::fs::write(root.path().join("Cargo.toml"), "[package]").unwrap();
+
+        let module_path = |path: &str| module_path(&root.path().join(path)).join("::");
+
+        assert_eq!(module_path("src/lib.rs"), "");
+        assert_eq!(module_path("src/main.rs"), "");
+        assert_eq!(module_path("src/cli.rs"), "cli");
+        assert_eq!(module_path("src/workers/mod.rs"), "workers");
+        assert_eq!(module_path("src/workers/report.rs"), "workers::report");
+        assert_eq!(module_path("src/bin.rs"), "bin");
+        assert_eq!(module_path("src/bin/tool.rs"), "");
+        assert_eq!(module_path("src/bin/tool/main.rs"), "");
+        assert_eq!(module_path("src/bin/tool/args.rs"), "args");
+        assert_eq!(module_path("tests/cli.rs"), "");
+        assert_eq!(module_path("tests/it/main.rs"), "");
+        assert_eq!(module_path("tests/it/parse.rs"), "parse");
+        assert_eq!(module_path("build.rs"), "");
+    }
+}
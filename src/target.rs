@@ -0,0 +1,119 @@
+use std::{
+    fs::File,
+    io,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+};
+
+use tempdir::TempDir;
+use tracing::{debug, warn};
+
+/// name of the lock file kept inside a persistent target directory
+const LOCK: &str = ".tarballin.lock";
+
+/// where coverage builds happen
+pub enum TargetDir {
+    /// a directory reused between sessions, exclusively held while the server runs
+    Persistent { path: PathBuf, _lock: File },
+
+    /// a throwaway directory, used when the persistent one belongs to another server
+    Temporary(TempDir),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum TargetError {
+    #[error("{0}")]
+    IO(#[from] io::Error),
+
+    #[error("{} is in use by another tarballin instance", .0.display())]
+    Locked(PathBuf),
+}
+
+impl TargetDir {
+    /// takes the persistent directory, falling back to a temporary one if it is locked
+    pub fn acquire(path: &Path) -> Result<Self, TargetError> {
+        match lock(path) {
+            Ok(lock) => {
+                debug!(path = %path.display(), "using persistent target dir");
+                Ok(TargetDir::Persistent {
+                    path: path.to_path_buf(),
+                    _lock: lock,
+                })
+            }
+
+            Err(TargetError::Locked(path)) => {
+                warn!(path = %path.display(), "target dir is locked, using a temporary one");
+                Ok(TargetDir::Temporary(TempDir::new("tarballin")?))
+            }
+
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            TargetDir::Persistent { path, .. } => path,
+            TargetDir::Temporary(dir) => dir.path(),
+        }
+    }
+}
+
+/// removes a persistent target directory, unless a running server holds it
+pub fn clean(path: &Path) -> Result<(), TargetError> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let lock = lock(path)?;
+    std::fs::remove_dir_all(path)?;
+    drop(lock);
+
+    Ok(())
+}
+
+fn lock(path: &Path) -> Result<File, TargetError> {
+    std::fs::create_dir_all(path)?;
+
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path.join(LOCK))?;
+
+    // SAFETY: the descriptor is owned by `file` and stays open for the call
+    let res = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+
+    if res != 0 {
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() == Some(libc::EWOULDBLOCK) {
+            return Err(TargetError::Locked(path.to_path_buf()));
+        }
+
+        return Err(error.into());
+    }
+
+    Ok(file)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lock() {
+        let root = TempDir::new("tarballin-test").unwrap();
+        let path = root.path().join("target");
+
+        let first = TargetDir::acquire(&path).unwrap();
+        assert!(matches!(first, TargetDir::Persistent { .. }));
+
+        let second = TargetDir::acquire(&path).unwrap();
+        assert!(matches!(second, TargetDir::Temporary(_)));
+
+        assert!(matches!(clean(&path), Err(TargetError::Locked(_))));
+
+        drop(first);
+        clean(&path).unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    io::{self, BufReader},
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    thread::{self, JoinHandle},
+};
+
+use crossbeam_channel::{bounded, Receiver, Sender};
+use lsp_server::{Connection, Message};
+use lsp_types::notification::{Exit, Notification as _};
+use tracing::{debug, trace};
+
+/// io threads backing a [`Connection`], either owned by lsp_server or by us
+pub enum IoThreads {
+    Lsp(lsp_server::IoThreads),
+    Socket {
+        reader: JoinHandle<io::Result<()>>,
+        writer: JoinHandle<io::Result<()>>,
+    },
+}
+
+impl IoThreads {
+    pub fn join(self) -> io::Result<()> {
+        match self {
+            IoThreads::Lsp(threads) => threads.join(),
+            IoThreads::Socket { reader, writer } => {
+                let read = reader
+                    .join()
+                    .unwrap_or_else(|_| Err(io::Error::other("reader thread panicked")));
+                let write = writer
+                    .join()
+                    .unwrap_or_else(|_| Err(io::Error::other("writer thread panicked")));
+
+                read.and(write)
+            }
+        }
+    }
+}
+
+impl From<lsp_server::IoThreads> for IoThreads {
+    fn from(threads: lsp_server::IoThreads) -> Self {
+        IoThreads::Lsp(threads)
+    }
+}
+
+pub fn unix_listen(path: &Path) -> io::Result<(Connection, IoThreads)> {
+    // a socket left behind by a previous instance would make bind fail
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if meta.file_type().is_socket() {
+            debug!(path = %path.display(), "removing stale socket");
+            std::fs::remove_file(path)?;
+        }
+    }
+
+    let listener = UnixListener::bind(path)?;
+    let accepted = listener.accept();
+
+    // only one client is ever served, so the socket file is no longer needed
+    drop(listener);
+    let _ = std::fs::remove_file(path);
+
+    let (stream, _) = accepted?;
+
+    socket_transport(stream)
+}
+
+pub fn unix_connect(path: &Path) -> io::Result<(Connection, IoThreads)> {
+    let stream = UnixStream::connect(path)?;
+
+    socket_transport(stream)
+}
+
+fn socket_transport(stream: UnixStream) -> io::Result<(Connection, IoThreads)> {
+    let (reader_tx, receiver) = bounded::<Message>(0);
+    let (sender, writer_rx) = bounded::<Message>(0);
+
+    let read_stream = stream.try_clone()?;
+    let reader = thread::spawn(move || read_messages(read_stream, reader_tx));
+    let writer = thread::spawn(move || write_messages(stream, writer_rx));
+
+    Ok((
+        Connection { sender, receiver },
+        IoThreads::Socket { reader, writer },
+    ))
+}
+
+fn read_messages(stream: UnixStream, tx: Sender<Message>) -> io::Result<()> {
+    let mut buf = BufReader::new(stream);
+
+    while let Some(msg) = Message::read(&mut buf)? {
+        let is_exit = matches!(&msg, Message::Notification(n) if n.method == Exit::METHOD);
+
+        if tx.send(msg).is_err() || is_exit {
+            break;
+        }
+    }
+
+    trace!("socket reader finished");
+
+    Ok(())
+}
+
+fn write_messages(mut stream: UnixStream, rx: Receiver<Message>) -> io::Result<()> {
+    for msg in rx {
+        msg.write(&mut stream)?;
+    }
+
+    trace!("socket writer finished");
+
+    let _ = stream.shutdown(std::net::Shutdown::Both);
+
+    Ok(())
+}
@@ -1,16 +1,21 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
 use crossbeam_channel::{Receiver, SendError, Sender};
 use lsp_server::{ExtractError, Message, Notification, Request, RequestId};
 use lsp_types::notification::{DidOpenTextDocument, DidSaveTextDocument, Exit};
 use lsp_types::request::{
-    DocumentDiagnosticRequest, Shutdown, WorkspaceDiagnosticRefresh, WorkspaceDiagnosticRequest,
+    CodeActionRequest, CodeLensRequest, DocumentDiagnosticRequest, ExecuteCommand, HoverRequest,
+    InlayHintRequest, SemanticTokensFullRequest, SemanticTokensRangeRequest, Shutdown,
+    WorkspaceDiagnosticRequest,
 };
 use lsp_types::{notification::Notification as _, request::Request as _};
 use serde::de::DeserializeOwned;
 use tracing::{error, info_span, trace, warn};
+use tree_sitter::Point;
 use url::Url;
 
+use crate::lsp_ext::{CoveredBy, Functions};
+
 use super::Trigger;
 
 #[derive(thiserror::Error, Debug)]
@@ -86,17 +91,113 @@ fn process(msg: Message, tx: &Sender<Trigger>) -> Result<(), IngestError> {
                 let (id, params) = extract_request::<DocumentDiagnosticRequest, _>(req)?;
                 let path = extract_file_url(params.text_document.uri)?;
 
-                tx.send(Trigger::DocDiag(id, path))?;
+                tx.send(Trigger::DocDiag(id, path, params.previous_result_id))?;
             }
 
             WorkspaceDiagnosticRequest::METHOD => {
                 trace!("workspace diagnostic request");
-                tx.send(Trigger::WorkDiag(req.id))?;
+
+                let (id, params) = extract_request::<WorkspaceDiagnosticRequest, _>(req)?;
+                let mut previous = HashMap::new();
+                for prev in params.previous_result_ids {
+                    let path = extract_file_url(prev.uri)?;
+                    previous.insert(path, prev.value);
+                }
+
+                tx.send(Trigger::WorkDiag(id, previous))?;
+            }
+
+            Functions::METHOD => {
+                trace!("functions request");
+
+                let (id, params) = extract_request::<Functions, _>(req)?;
+                let path = params
+                    .text_document
+                    .map(|doc| extract_file_url(doc.uri))
+                    .transpose()?;
+
+                tx.send(Trigger::Functions(id, path))?;
+            }
+
+            HoverRequest::METHOD => {
+                trace!("hover request");
+
+                let (id, params) = extract_request::<HoverRequest, _>(req)?;
+                let position = params.text_document_position_params;
+                let path = extract_file_url(position.text_document.uri)?;
+
+                tx.send(Trigger::Hover(id, path, position.position.line + 1))?;
+            }
+
+            InlayHintRequest::METHOD => {
+                trace!("inlay hint request");
+
+                let (id, params) = extract_request::<InlayHintRequest, _>(req)?;
+                let path = extract_file_url(params.text_document.uri)?;
+                let lines = (params.range.start.line + 1, params.range.end.line + 1);
+
+                tx.send(Trigger::InlayHints(id, path, lines))?;
+            }
+
+            SemanticTokensFullRequest::METHOD => {
+                trace!("semantic tokens request");
+
+                let (id, params) = extract_request::<SemanticTokensFullRequest, _>(req)?;
+                let path = extract_file_url(params.text_document.uri)?;
+
+                tx.send(Trigger::SemanticTokens(id, path, None))?;
             }
 
-            WorkspaceDiagnosticRefresh::METHOD => {
-                trace!("workspace diagnostic refresh");
-                tx.send(Trigger::WorkDiagRefresh(req.id))?;
+            SemanticTokensRangeRequest::METHOD => {
+                trace!("semantic tokens range request");
+
+                let (id, params) = extract_request::<SemanticTokensRangeRequest, _>(req)?;
+                let path = extract_file_url(params.text_document.uri)?;
+                let lines = (params.range.start.line + 1, params.range.end.line + 1);
+
+                tx.send(Trigger::SemanticTokens(id, path, Some(lines)))?;
+            }
+
+            CodeActionRequest::METHOD => {
+                trace!("code action request");
+
+                let (id, params) = extract_request::<CodeActionRequest, _>(req)?;
+                let path = extract_file_url(params.text_document.uri)?;
+                let start = params.range.start;
+                let coverage = params
+                    .context
+                    .diagnostics
+                    .iter()
+                    .any(|diag| diag.source.as_deref() == Some("lsp-tarpaulin"));
+
+                let point = Point::new(start.line as usize, start.character as usize);
+                tx.send(Trigger::CodeAction(id, path, point, coverage))?;
+            }
+
+            CodeLensRequest::METHOD => {
+                trace!("code lens request");
+
+                let (id, params) = extract_request::<CodeLensRequest, _>(req)?;
+                let path = extract_file_url(params.text_document.uri)?;
+
+                tx.send(Trigger::CodeLens(id, path))?;
+            }
+
+            ExecuteCommand::METHOD => {
+                trace!("execute command request");
+
+                let (id, params) = extract_request::<ExecuteCommand, _>(req)?;
+
+                tx.send(Trigger::Execute(id, params.command, params.arguments))?;
+            }
+
+            CoveredBy::METHOD => {
+                trace!("covered by request");
+
+                let (id, params) = extract_request::<CoveredBy, _>(req)?;
+                let path = extract_file_url(params.text_document.uri)?;
+
+                tx.send(Trigger::CoveredBy(id, path, params.position.line + 1))?;
             }
 
             Shutdown::METHOD => {
@@ -1,6 +1,7 @@
 use lsp_server::RequestId;
-use lsp_types::MessageType;
-use std::path::PathBuf;
+use lsp_types::{Diagnostic, MessageType};
+use std::{collections::HashMap, path::PathBuf, time::SystemTime};
+use tree_sitter::Point;
 
 mod ingest;
 mod process;
@@ -10,12 +11,44 @@ pub use ingest::run as ingest;
 pub use process::run as process;
 pub use report::run as report;
 
-use crate::coverage::Trace;
+use crate::{
+    config::Config,
+    coverage::{Source, Trace},
+    ignore::Ignore,
+    index::Indexer,
+};
+
+/// what the process worker knows about the project it serves
+pub struct Project {
+    pub source: Source,
+    /// where single test runs report, none when the report path is configured
+    pub test_source: Option<Source>,
+    pub root: PathBuf,
+    pub target: PathBuf,
+    pub workspaces: Vec<PathBuf>,
+    pub ignore: Ignore,
+    pub indexer: Option<Indexer>,
+    pub config: Config,
+}
 
 pub enum Trigger {
-    DocDiag(RequestId, PathBuf),
-    WorkDiag(RequestId),
-    WorkDiagRefresh(RequestId),
+    DocDiag(RequestId, PathBuf, Option<String>),
+    WorkDiag(RequestId, HashMap<PathBuf, String>),
+    /// function coverage of one file, or all of them
+    Functions(RequestId, Option<PathBuf>),
+    /// what is known about a one based line
+    Hover(RequestId, PathBuf, u32),
+    /// execution counts for the one based lines from first to last
+    InlayHints(RequestId, PathBuf, (u32, u32)),
+    /// the coverage heatmap of a file, or of its one based lines from first to last
+    SemanticTokens(RequestId, PathBuf, Option<(u32, u32)>),
+    CodeLens(RequestId, PathBuf),
+    /// fixes for the coverage diagnostics at a point, `false` when there are none there
+    CodeAction(RequestId, PathBuf, Point, bool),
+    /// the tests that execute a one based line
+    CoveredBy(RequestId, PathBuf, u32),
+    /// a `workspace/executeCommand` with its arguments
+    Execute(RequestId, String, Vec<serde_json::Value>),
     Write(PathBuf),
     Open(PathBuf),
     Exit(RequestId),
@@ -23,6 +56,52 @@ pub enum Trigger {
 
 pub enum Report {
     Plain(PathBuf, Vec<Trace>),
+    Diagnostics(PathBuf, Vec<Diagnostic>),
+    Document(RequestId, PathBuf, Vec<Trace>, Option<String>),
+    Unchanged(RequestId, String),
+    Workspace(RequestId, Vec<FileReport>),
+    Functions(RequestId, Vec<(PathBuf, Vec<Trace>)>),
+    Hover(RequestId, PathBuf, Option<Hovered>),
+    InlayHints(RequestId, PathBuf, Vec<Trace>, (u32, u32)),
+    /// the traces the ignore rules leave and the lines they took out
+    SemanticTokens(RequestId, PathBuf, Vec<Trace>, Vec<u32>, Option<(u32, u32)>),
+    CodeLens(RequestId, PathBuf, Vec<Trace>),
+    CodeActions(RequestId, Option<Actions>),
+    CoveredBy(RequestId, Vec<String>),
+    /// a request that was handled and has nothing to answer with
+    Done(RequestId),
+    Refresh,
+    Failed(RequestId, String),
     Message(MessageType, String),
     Exit(RequestId),
 }
+
+pub enum FileReport {
+    Full(PathBuf, Vec<Trace>, String),
+    Unchanged(PathBuf, String),
+}
+
+/// where code actions apply
+pub struct Actions {
+    pub path: PathBuf,
+    /// as ignore rules see it
+    pub relative: PathBuf,
+    pub point: Point,
+    /// the file new ignore rules go to
+    pub ignore_file: PathBuf,
+}
+
+/// the coverage around a hovered line
+pub struct Hovered {
+    /// one based
+    pub line: u32,
+    /// the traces of the whole file the ignore rules leave
+    pub traces: Vec<Trace>,
+    /// the ignore rule that took the line out
+    pub ignored: Option<String>,
+    /// what the index knows to execute the line
+    pub tests: Vec<String>,
+    pub generation: usize,
+    /// when the coverage was collected, as far as it is known
+    pub collected: Option<SystemTime>,
+}
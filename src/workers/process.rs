@@ -1,29 +1,80 @@
 use std::{
-    collections::HashSet,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
     fs::File,
+    hash::{Hash, Hasher},
     path::{Path, PathBuf},
+    time::{Duration, SystemTime},
 };
 
-use crossbeam_channel::{bounded, select, Receiver, SendError, Sender};
+use crossbeam_channel::{bounded, never, select, tick, Receiver, SendError, Sender};
 use lsp_types::MessageType;
 use tracing::{debug, error, info_span, trace};
 
 use crate::{
-    coverage::Coverage,
-    ignore::Ignore,
-    runner::{runner_thread, Input, Status},
+    compiler,
+    coverage::{Coverage, Source, Trace},
+    diff::Changes,
+    ignore::{self, Ignore},
+    index::{Index, Update},
+    libtest, lsp_ext,
+    mode::Mode,
+    runner::{runner_thread, Filter, Input, Status},
+    summary::{self, Totals},
 };
 
-use super::{Report, Trigger};
+use super::{Actions, FileReport, Hovered, Project, Report, Trigger};
+
+/// how often an offline coverage file is checked for changes
+const POLL: Duration = Duration::from_secs(1);
 
 struct State {
-    package: String,
-    target: PathBuf,
+    source: Source,
+    mode: Mode,
     generation: usize,
+    revisions: HashMap<PathBuf, Revision>,
     ignore: Ignore,
     coverage: Option<Coverage>,
+    /// the coverage of a single test, shown instead of the full coverage while set
+    layer: Option<Layer>,
+    /// where single test runs report, none when the report path is configured
+    test_source: Option<Source>,
+    /// which tests execute each line, when indexing is enabled
+    index: Option<Index>,
+    /// when the current coverage was collected
+    collected: Option<SystemTime>,
     interest: HashSet<PathBuf>,
+    /// files with build problems pushed to the client, cleared on the next run
+    problems: HashSet<PathBuf>,
+    root: PathBuf,
     workspaces: Vec<PathBuf>,
+    /// set in offline mode, where the coverage file is all there is
+    watch: Option<Watch>,
+    /// set when only changed lines are flagged
+    diff: Option<Diff>,
+}
+
+/// the git base diagnostics are limited to and what changed since, as of the last publish
+struct Diff {
+    base: String,
+    changes: Changes,
+}
+
+/// what the tests matching a filter cover on their own
+struct Layer {
+    test: String,
+    coverage: Coverage,
+}
+
+/// the last seen modification of a coverage file someone else writes
+struct Watch {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+/// the generation a file's diagnostics last changed in
+struct Revision {
+    digest: u64,
+    generation: usize,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -50,73 +101,98 @@ impl<T> From<SendError<T>> for ProcessError {
     }
 }
 
-pub fn run(
-    package: String,
-    target: PathBuf,
-    workspaces: Vec<PathBuf>,
-    ignore: Ignore,
-    rx: Receiver<Trigger>,
-    tx: Sender<Report>,
-) {
+pub fn run(project: Project, mode: Mode, rx: Receiver<Trigger>, tx: Sender<Report>) {
     let _span = info_span!("process worker").entered();
 
+    let Project {
+        source,
+        test_source,
+        root,
+        target,
+        workspaces,
+        ignore,
+        indexer,
+        config,
+    } = project;
+
+    let offline = config.coverage.offline;
+
     let (input_tx, input_rx) = bounded(1);
     let (status_tx, status_rx) = bounded(1);
 
-    let handle = {
-        let target = target.clone();
-        std::thread::spawn(|| runner_thread(target, input_rx, status_tx))
+    let handle = if offline {
+        debug!(path = %source.path().display(), "offline, not running cargo");
+        None
+    } else {
+        let runner = config.runner.clone();
+        Some(std::thread::spawn(|| {
+            runner_thread(target, runner, input_rx, status_tx)
+        }))
     };
 
     let mut coverage = None;
-    for workspace in &workspaces {
+    let mut collected = None;
+    for workspace in workspaces.iter().filter(|_| !offline) {
         let mut path = workspace.to_path_buf();
         path.push("target");
         path.push(".tarballin-cache.json");
 
         let Ok(file) = File::open(path) else { continue };
-        let Ok(cov) = serde_json::from_reader(file) else {
+        let Ok(cov) = serde_json::from_reader(&file) else {
             continue;
         };
 
         debug!("loaded cached coverage");
         coverage = Some(cov);
+        collected = file.metadata().and_then(|meta| meta.modified()).ok();
         break;
     }
 
     debug!(loaded = coverage.is_some(), "using cached coverage");
 
+    // the indexer kills what it runs and stops once the cancel sender is dropped
+    let (index_tx, index_rx) = bounded(1);
+    let (cancel_tx, cancel_rx) = bounded::<()>(0);
+    let (mut updates, indexing) = match indexer {
+        Some(indexer) => (
+            index_rx,
+            Some(std::thread::spawn(move || indexer.run(index_tx, cancel_rx))),
+        ),
+        None => (never(), None),
+    };
+
+    let watch = offline.then(|| Watch {
+        path: source.path(),
+        modified: None,
+    });
+    let ticks = if offline { tick(POLL) } else { never() };
+
     let interest = HashSet::new();
     let mut state = State {
         ignore,
-        target,
-        package,
+        source,
+        mode,
         generation: 1,
+        revisions: HashMap::new(),
         coverage,
+        layer: None,
+        test_source,
+        index: None,
+        collected,
         interest,
+        problems: HashSet::new(),
+        root,
         workspaces,
+        watch,
+        diff: config.diff.base.map(|base| Diff {
+            base,
+            changes: Changes::default(),
+        }),
     };
 
-    if let Some(cov) = &state.coverage {
-        for (path, traces) in &cov.traces {
-            let path: PathBuf = path.into();
-            let result = state.ignore.matches(state.strip_workspaces(&path));
-            debug!(?result, "ignore result");
-
-            let Ok(content) =
-                std::fs::read(&path).map_err(|e| ProcessError::FailedRead(path.clone(), e))
-            else {
-                continue;
-            };
-
-            let Ok(traces) = result.filter(&content, traces) else {
-                continue;
-            };
-
-            if tx.send(Report::Plain(path, traces)).is_err() {
-                return;
-            }
-        }
+    // publishes what is known, after reading the report first when offline
+    if state.reload(&tx).is_err() {
+        return;
     }
 
     loop {
@@ -130,6 +206,17 @@ pub fn run(
                 let Ok(status) = status else { break; };
                 handle_status(&mut state, status, &tx)
             }
+
+            recv(ticks) -> _ => state.reload(&tx),
+
+            recv(updates) -> update => match update {
+                Ok(update) => handle_update(&mut state, update, &tx),
+                // the indexer gave up, what is already known stays
+                Err(_) => {
+                    updates = never();
+                    Ok(())
+                }
+            },
         };
 
         if matches!(result, Err(ProcessError::ChannelClose)) {
@@ -142,7 +229,17 @@ pub fn run(
         }
     }
 
-    handle.join().unwrap();
+    // closing the input lets the runner finish if the editor went away without a shutdown
+    drop(input_tx);
+    if let Some(handle) = handle {
+        handle.join().unwrap();
+    }
+
+    drop(cancel_tx);
+    drop(updates);
+    if let Some(indexing) = indexing {
+        indexing.join().unwrap();
+    }
 }
 
 fn handle_trigger(
@@ -152,35 +249,255 @@ fn handle_trigger(
     tx: &Sender<Report>,
 ) -> Result<(), ProcessError> {
     match trigger {
-        Trigger::WorkDiagRefresh(_) => todo!(),
-        Trigger::Write(_) => {
-            input_tx.send(Input::Run)?;
+        Trigger::Write(path) => {
+            debug!(path = %path.display(), "saved file");
+
+            if ignore::is_project_file(&state.root, &path) {
+                debug!("reloading ignore rules");
+                state.ignore = Ignore::project(&state.root);
+                state.generation += 1;
+                return state.publish(tx);
+            }
+
+            if state.watch.is_none() {
+                input_tx.send(Input::Run(None))?;
+            }
         }
 
         Trigger::Open(path) => {
-            let coverage = Coverage::load(&state.package, &state.target)?;
-            //let path = state.strip_workspaces(path);
-            let result = state.ignore.matches(state.strip_workspaces(&path));
-            debug!(?result, "ignore result");
+            if !matches!(state.mode, Mode::Adhoc) {
+                trace!("diagnostics are pulled");
+                return Ok(());
+            }
+
+            let Some(coverage) = state.shown() else {
+                trace!("no coverage to show yet");
+                return Ok(());
+            };
 
             let Some(traces) = coverage.traces.get(&path) else {
                 return Err(ProcessError::MissingTrace(path));
             };
 
-            let content =
-                std::fs::read(&path).map_err(|e| ProcessError::FailedRead(path.clone(), e))?;
-
-            let traces = result.filter(&content, traces)?;
+            let traces = state.diagnosed(&path, traces)?;
 
             tx.send(Report::Plain(path, traces))?;
         }
-        Trigger::DocDiag(_, _) => todo!(),
-        Trigger::WorkDiag(_) => todo!(),
+
+        Trigger::DocDiag(id, path, previous) => {
+            let Some(cov) = state.shown() else {
+                trace!("no coverage to diagnose with");
+                tx.send(Report::Document(id, path, Vec::new(), None))?;
+                return Ok(());
+            };
+
+            let result_id = state.result_id(&path);
+            if previous.as_ref() == Some(&result_id) {
+                tx.send(Report::Unchanged(id, result_id))?;
+                return Ok(());
+            }
+
+            let traces = match cov.traces.get(&path) {
+                Some(traces) => match state.diagnosed(&path, traces) {
+                    Ok(traces) => traces,
+                    Err(error) => {
+                        tx.send(Report::Failed(id, error.to_string()))?;
+                        return Err(error);
+                    }
+                },
+                None => Vec::new(),
+            };
+
+            tx.send(Report::Document(id, path, traces, Some(result_id)))?;
+        }
+
+        Trigger::WorkDiag(id, previous) => {
+            let mut files = Vec::new();
+
+            if let Some(cov) = state.shown() {
+                for (path, traces) in &cov.traces {
+                    let result_id = state.result_id(path);
+                    if previous.get(path) == Some(&result_id) {
+                        files.push(FileReport::Unchanged(path.clone(), result_id));
+                        continue;
+                    }
+
+                    match state.diagnosed(path, traces) {
+                        Ok(traces) => files.push(FileReport::Full(path.clone(), traces, result_id)),
+                        Err(error) => error!(%error, "failed to filter coverage"),
+                    }
+                }
+            }
+
+            tx.send(Report::Workspace(id, files))?;
+        }
+
+        Trigger::Functions(id, path) => {
+            let mut files = Vec::new();
+
+            if let Some(cov) = state.shown() {
+                let traces = cov
+                    .traces
+                    .iter()
+                    .filter(|(p, _)| path.as_ref().is_none_or(|path| path == *p));
+
+                for (path, traces) in traces {
+                    match state.unignored(path, traces) {
+                        Ok(traces) => files.push((path.clone(), traces)),
+                        Err(error) => error!(%error, "failed to filter coverage"),
+                    }
+                }
+            }
+
+            tx.send(Report::Functions(id, files))?;
+        }
+
+        Trigger::Hover(id, path, line) => {
+            let hovered = match state.shown() {
+                Some(cov) => match cov.traces.get(&path) {
+                    Some(traces) => Some(state.hover(&path, traces, line)?),
+                    None => None,
+                },
+                None => None,
+            };
+
+            tx.send(Report::Hover(id, path, hovered))?;
+        }
+
+        Trigger::InlayHints(id, path, lines) => {
+            let traces = match state.shown().and_then(|cov| cov.traces.get(&path)) {
+                Some(traces) => state.unignored(&path, traces)?,
+                None => Vec::new(),
+            };
+
+            tx.send(Report::InlayHints(id, path, traces, lines))?;
+        }
+
+        Trigger::SemanticTokens(id, path, range) => {
+            let (traces, ignored) = match state.shown().and_then(|cov| cov.traces.get(&path)) {
+                Some(traces) => state.heatmap(&path, traces)?,
+                None => (Vec::new(), Vec::new()),
+            };
+
+            tx.send(Report::SemanticTokens(id, path, traces, ignored, range))?;
+        }
+
+        Trigger::CodeAction(id, path, point, coverage) => {
+            let actions = coverage.then(|| Actions {
+                relative: state.strip_workspaces(&path).to_path_buf(),
+                path,
+                point,
+                ignore_file: ignore::project_file(&state.root),
+            });
+
+            tx.send(Report::CodeActions(id, actions))?;
+        }
+
+        Trigger::CodeLens(id, path) => {
+            let traces = match state.shown().and_then(|cov| cov.traces.get(&path)) {
+                Some(traces) => state.unignored(&path, traces)?,
+                None => Vec::new(),
+            };
+
+            tx.send(Report::CodeLens(id, path, traces))?;
+        }
+
+        Trigger::Execute(id, command, arguments) => {
+            // every command takes a single string, a test filter or an ignore rule, a test filter
+            // can be followed by whether it is the exact name of a test
+            let argument = arguments
+                .first()
+                .and_then(|argument| argument.as_str())
+                .map(str::to_string);
+            let exact = arguments.get(1).and_then(|exact| exact.as_bool());
+
+            match command.as_str() {
+                lsp_ext::ADD_IGNORE => {
+                    let Some(rule) = argument else {
+                        tx.send(Report::Failed(id, "missing ignore rule".to_string()))?;
+                        return Ok(());
+                    };
+
+                    if let Err(error) = state.ignore.extend(rule.as_bytes()) {
+                        tx.send(Report::Failed(id, error.to_string()))?;
+                        return Ok(());
+                    }
+
+                    state.generation += 1;
+                    state.publish(tx)?;
+                    tx.send(Report::Done(id))?;
+                    return Ok(());
+                }
+
+                lsp_ext::SHOW_ALL => {
+                    if let Some(layer) = state.layer.take() {
+                        debug!(test = layer.test, "back to the full coverage");
+                        state.generation += 1;
+                        state.publish(tx)?;
+                    }
+
+                    tx.send(Report::Done(id))?;
+                    return Ok(());
+                }
+
+                lsp_ext::RUN_TEST if argument.is_none() => {
+                    tx.send(Report::Failed(id, "missing test name".to_string()))?;
+                    return Ok(());
+                }
+
+                lsp_ext::RUN | lsp_ext::RUN_TEST => (),
+
+                _ => {
+                    tx.send(Report::Failed(id, format!("unknown command {command}")))?;
+                    return Ok(());
+                }
+            }
+
+            if state.watch.is_some() {
+                tx.send(Report::Failed(
+                    id,
+                    "coverage is read from a file, there is nothing to run".to_string(),
+                ))?;
+                return Ok(());
+            }
+
+            if argument.is_some() && state.test_source.is_none() {
+                tx.send(Report::Failed(
+                    id,
+                    "single test runs need the report in the target dir, unset `coverage.path`"
+                        .to_string(),
+                ))?;
+                return Ok(());
+            }
+
+            debug!(filter = ?argument, ?exact, "running coverage on request");
+
+            let filter = argument.map(|name| Filter {
+                name,
+                exact: exact.unwrap_or(false),
+            });
+            input_tx.send(Input::Run(filter))?;
+            tx.send(Report::Done(id))?;
+        }
+
+        Trigger::CoveredBy(id, path, line) => {
+            let Some(index) = &state.index else {
+                tx.send(Report::Failed(
+                    id,
+                    "no test index, enable it with `index.enabled`".to_string(),
+                ))?;
+                return Ok(());
+            };
+
+            tx.send(Report::CoveredBy(id, index.tests(&path, line)))?;
+        }
 
         Trigger::Exit(id) => {
             trace!("exiting process worker");
             tx.send(Report::Exit(id))?;
-            input_tx.send(Input::Exit)?;
+            if state.watch.is_none() {
+                input_tx.send(Input::Exit)?;
+            }
             return Err(ProcessError::ChannelClose);
         }
     }
@@ -188,37 +505,95 @@ fn handle_trigger(
     Ok(())
 }
 
+fn handle_update(
+    state: &mut State,
+    update: Update,
+    tx: &Sender<Report>,
+) -> Result<(), ProcessError> {
+    match update {
+        Update::Loaded(index) => state.index = Some(index),
+        Update::Indexed(test) => trace!(test, "indexed test"),
+        Update::Done(index) => {
+            tx.send(Report::Message(
+                MessageType::INFO,
+                "finished indexing which tests cover each line".to_string(),
+            ))?;
+            state.index = Some(index);
+        }
+    }
+
+    Ok(())
+}
+
 fn handle_status(
     state: &mut State,
     status: Status,
     tx: &Sender<Report>,
 ) -> Result<(), ProcessError> {
     match status {
-        Status::Success => {
+        Status::Success(filter) => {
             tracing::debug!("successful coverage found");
             state.generation += 1;
-            state.coverage = Coverage::load(&state.package, &state.target).ok();
-            for workspace in &state.workspaces {
-                let _ = cache(&state.package, &state.target, workspace);
+            state.collected = Some(SystemTime::now());
+
+            // a filtered run only knows part of the picture, it must not replace the full coverage
+            if let (Some(test), Some(source)) = (filter, &state.test_source) {
+                let coverage = source.load()?;
+                tx.send(Report::Message(
+                    MessageType::INFO,
+                    format!("showing what tests matching `{test}` cover"),
+                ))?;
+
+                state.layer = Some(Layer { test, coverage });
+                return state.publish(tx);
             }
 
-            if let Some(cov) = &state.coverage {
-                for (path, traces) in &cov.traces {
-                    let path: PathBuf = path.into();
-                    let result = state.ignore.matches(state.strip_workspaces(&path));
-                    debug!(?result, "ignore result");
+            state.coverage = state.source.load().ok();
+            if let Some(coverage) = &state.coverage {
+                for workspace in &state.workspaces {
+                    let _ = cache(coverage, workspace);
+                }
+            }
+
+            state.publish(tx)?;
+        }
+        Status::Failure(output) => {
+            tracing::debug!("failed coverage found");
+
+            let errors = compiler::errors(&output.stdout);
+            if !errors.is_empty() {
+                tx.send(Report::Message(
+                    MessageType::ERROR,
+                    format!(
+                        "tarpaulin failed to build the crate: {} compiler error(s)",
+                        errors.len()
+                    ),
+                ))?;
+
+                for (path, diags) in compiler::diagnostics(&errors, &state.root) {
+                    state.problems.insert(path.clone());
+                    tx.send(Report::Diagnostics(path, diags))?;
+                }
 
-                    let content = std::fs::read(&path)
-                        .map_err(|e| ProcessError::FailedRead(path.clone(), e))?;
+                return Ok(());
+            }
 
-                    let traces = result.filter(&content, traces)?;
+            let failures = libtest::failures(&format!("{}\n{}", output.stdout, output.stderr));
+            if !failures.is_empty() {
+                tx.send(Report::Message(
+                    MessageType::ERROR,
+                    format!("{} test(s) failed under tarpaulin", failures.len()),
+                ))?;
 
-                    tx.send(Report::Plain(path, traces))?;
+                for (path, diags) in libtest::diagnostics(&failures, &state.root) {
+                    state.problems.insert(path.clone());
+                    tx.send(Report::Diagnostics(path, diags))?;
                 }
+
+                return Ok(());
             }
-        }
-        Status::Failure => {
-            tracing::debug!("failed coverage found");
+
+            debug!(stderr = output.stderr, "tarpaulin output");
             tx.send(Report::Message(
                 MessageType::ERROR,
                 "tarpaulin failed to run".to_string(),
@@ -231,35 +606,251 @@ fn handle_status(
                 "restarting tarpaulin run".to_string(),
             ))?;
         }
-        Status::Starting => {
+        Status::Starting(filter) => {
             tracing::debug!("starting coverage run");
             tx.send(Report::Message(
                 MessageType::INFO,
                 "starting tarpaulin".to_string(),
             ))?;
-            state.coverage = None;
+            if filter.is_none() {
+                state.coverage = None;
+            }
             state.interest.clear();
+
+            for path in state.problems.drain() {
+                tx.send(Report::Diagnostics(path, Vec::new()))?;
+            }
         }
     }
 
     Ok(())
 }
 
-fn cache(package: &str, target: &Path, workspace: &Path) -> std::io::Result<()> {
-    let mut src = target.to_path_buf();
-    src.push("tarpaulin");
-    src.push(format!("{package}-coverage.json"));
-
+/// keeps the converted coverage around so the next session starts with it, whatever format it came in
+fn cache(coverage: &Coverage, workspace: &Path) -> Result<(), ProcessError> {
     let mut dst = workspace.to_path_buf();
     dst.push("target");
     dst.push(".tarballin-cache.json");
 
-    std::fs::copy(src, dst)?;
+    let file = File::create(&dst).map_err(|e| ProcessError::FailedRead(dst, e))?;
+    serde_json::to_writer(file, coverage).map_err(crate::Error::from)?;
 
     Ok(())
 }
 
 impl State {
+    /// picks up an offline coverage file that changed since it was last read, otherwise
+    /// publishes what is already known
+    fn reload(&mut self, tx: &Sender<Report>) -> Result<(), ProcessError> {
+        let Some(watch) = &mut self.watch else {
+            return self.publish(tx);
+        };
+
+        let modified = std::fs::metadata(&watch.path)
+            .and_then(|meta| meta.modified())
+            .ok();
+
+        if modified.is_none() || modified == watch.modified {
+            return Ok(());
+        }
+
+        debug!(path = %watch.path.display(), "coverage file changed");
+
+        match self.source.load() {
+            Ok(coverage) => {
+                // only a report that could be read counts as seen, a half written one is read again
+                watch.modified = modified;
+                self.generation += 1;
+                self.coverage = Some(coverage);
+                self.collected = modified;
+                self.publish(tx)
+            }
+            Err(error) => {
+                error!(%error, "failed to read coverage file");
+                Ok(())
+            }
+        }
+    }
+
+    /// brings the file revisions up to date with the current coverage and lets the client know
+    ///
+    /// workspace clients pull diagnostics after a refresh, document clients pull them on their own
+    /// and adhoc clients get them pushed
+    fn publish(&mut self, tx: &Sender<Report>) -> Result<(), ProcessError> {
+        if self.shown().is_none() {
+            return Ok(());
+        }
+
+        if let Some(diff) = &mut self.diff {
+            match Changes::load(&self.root, &diff.base) {
+                Ok(changes) => diff.changes = changes,
+                Err(error) => error!(%error, base = diff.base, "failed to diff against base"),
+            }
+        }
+
+        let Some(cov) = self.shown() else {
+            return Ok(());
+        };
+
+        // files that dropped out, e.g. when switching between a test and the full coverage
+        let gone = self
+            .revisions
+            .keys()
+            .filter(|path| !cov.traces.contains_key(*path))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut files = Vec::with_capacity(cov.traces.len());
+        let mut patch = Totals::default();
+        for (path, traces) in &cov.traces {
+            match self.diagnosed(path, traces) {
+                Ok(traces) => {
+                    patch += Totals::of(summary::lines(&traces).values());
+                    files.push((path.clone(), traces));
+                }
+                Err(error) => error!(%error, "failed to filter coverage"),
+            }
+        }
+
+        if let Some(diff) = &self.diff {
+            let message = if patch.total == 0 {
+                format!("no covered code changed since {}", diff.base)
+            } else {
+                format!(
+                    "patch coverage {:.2}% ({}/{} changed lines) since {}",
+                    patch.percent(),
+                    patch.covered,
+                    patch.total,
+                    diff.base
+                )
+            };
+
+            tx.send(Report::Message(MessageType::INFO, message))?;
+        }
+
+        for (path, traces) in files {
+            let mut hasher = DefaultHasher::new();
+            traces.hash(&mut hasher);
+            let digest = hasher.finish();
+
+            match self.revisions.get_mut(&path) {
+                Some(rev) if rev.digest == digest => (),
+                _ => {
+                    let generation = self.generation;
+                    self.revisions
+                        .insert(path.clone(), Revision { digest, generation });
+                }
+            }
+
+            // the other modes pull diagnostics, pushing them too would show them twice
+            if matches!(self.mode, Mode::Adhoc) {
+                tx.send(Report::Plain(path, traces))?;
+            }
+        }
+
+        for path in gone {
+            self.revisions.remove(&path);
+
+            if matches!(self.mode, Mode::Adhoc) {
+                tx.send(Report::Plain(path, Vec::new()))?;
+            }
+        }
+
+        if matches!(self.mode, Mode::Workspace) {
+            tx.send(Report::Refresh)?;
+        }
+
+        Ok(())
+    }
+
+    /// the coverage of the test layer if there is one, otherwise the full coverage
+    fn shown(&self) -> Option<&Coverage> {
+        match &self.layer {
+            Some(layer) => Some(&layer.coverage),
+            None => self.coverage.as_ref(),
+        }
+    }
+
+    fn result_id(&self, path: &Path) -> String {
+        let generation = match self.revisions.get(path) {
+            Some(rev) => rev.generation,
+            None => self.generation,
+        };
+
+        generation.to_string()
+    }
+
+    /// the traces diagnostics are built from, what the ignore rules leave minus unchanged lines
+    /// when diffing
+    ///
+    /// everything else shows the whole file, counting only changed lines would skew its numbers
+    fn diagnosed(&self, path: &Path, traces: &[Trace]) -> Result<Vec<Trace>, ProcessError> {
+        let mut traces = self.unignored(path, traces)?;
+        if let Some(diff) = &self.diff {
+            traces.retain(|trace| diff.changes.contains(path, trace.line));
+        }
+
+        Ok(traces)
+    }
+
+    /// the traces the ignore rules leave of a file
+    fn unignored(&self, path: &Path, traces: &[Trace]) -> Result<Vec<Trace>, ProcessError> {
+        let result = self.ignore.matches(self.strip_workspaces(path));
+        debug!(?result, "ignore result");
+
+        let content =
+            std::fs::read(path).map_err(|e| ProcessError::FailedRead(path.to_path_buf(), e))?;
+
+        Ok(result.filter(&content, traces)?)
+    }
+
+    /// the traces the ignore rules leave of a file along with the lines the ignore rules took out
+    fn heatmap(
+        &self,
+        path: &Path,
+        traces: &[Trace],
+    ) -> Result<(Vec<Trace>, Vec<u32>), ProcessError> {
+        let kept = self.unignored(path, traces)?;
+
+        let mut ignored = traces
+            .iter()
+            .map(|trace| trace.line)
+            .filter(|line| !kept.iter().any(|trace| trace.line == *line))
+            .collect::<Vec<_>>();
+        ignored.dedup();
+
+        Ok((kept, ignored))
+    }
+
+    /// the traces around a line, and the rule that ignored it if that is why it is gone
+    fn hover(&self, path: &Path, traces: &[Trace], line: u32) -> Result<Hovered, ProcessError> {
+        let unignored = self.unignored(path, traces)?;
+
+        let traced = traces.iter().any(|trace| trace.line == line);
+        let kept = unignored.iter().any(|trace| trace.line == line);
+        let ignored = if traced && !kept {
+            let content =
+                std::fs::read(path).map_err(|e| ProcessError::FailedRead(path.to_path_buf(), e))?;
+            self.ignore
+                .reason(self.strip_workspaces(path), &content, line)?
+        } else {
+            None
+        };
+
+        Ok(Hovered {
+            line,
+            traces: unignored,
+            ignored,
+            tests: self
+                .index
+                .as_ref()
+                .map(|index| index.tests(path, line))
+                .unwrap_or_default(),
+            generation: self.generation,
+            collected: self.collected,
+        })
+    }
+
     fn strip_workspaces<'a>(&self, path: &'a Path) -> &'a Path {
         for workspace in &self.workspaces {
             if let Ok(p) = path.strip_prefix(workspace) {
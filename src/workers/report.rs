@@ -1,18 +1,40 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use crossbeam_channel::{Receiver, SendError, Sender};
-use lsp_server::{Message, Notification, Response};
+use lsp_server::{ErrorCode, Message, Notification, Request, RequestId, Response};
 use lsp_types::{
     notification::{PublishDiagnostics, ShowMessage},
-    Diagnostic, DiagnosticSeverity, MessageType, Position, PublishDiagnosticsParams, Range,
-    ShowMessageParams,
+    request::WorkspaceDiagnosticRefresh,
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeLens, Command, CreateFile, Diagnostic,
+    DiagnosticSeverity, DocumentChangeOperation, DocumentChanges, DocumentDiagnosticReport,
+    DocumentDiagnosticReportResult, FullDocumentDiagnosticReport, Hover, HoverContents, InlayHint,
+    InlayHintLabel, MarkupContent, MarkupKind, MessageType, NumberOrString, OneOf,
+    OptionalVersionedTextDocumentIdentifier, Position, PublishDiagnosticsParams, Range,
+    RelatedFullDocumentDiagnosticReport, RelatedUnchangedDocumentDiagnosticReport, ResourceOp,
+    SemanticTokens, ShowMessageParams, TextDocumentEdit, TextEdit,
+    UnchangedDocumentDiagnosticReport, WorkspaceDiagnosticReport, WorkspaceDiagnosticReportResult,
+    WorkspaceDocumentDiagnosticReport, WorkspaceEdit, WorkspaceFullDocumentDiagnosticReport,
+    WorkspaceUnchangedDocumentDiagnosticReport,
 };
 use tracing::{error, info_span, trace};
+use tree_sitter::Point;
 use url::Url;
 
-use crate::{coverage::Trace, line_slice::LineSlice};
+use crate::{
+    config::{DiagnosticsConfig, HintsConfig},
+    coverage::{Span, Trace},
+    group, heatmap,
+    ignore::{self, Suggestion},
+    line_slice::LineSlice,
+    lsp_ext::{self, FunctionCoverage},
+    summary, syntax,
+};
 
-use super::Report;
+use super::{Actions, FileReport, Hovered, Report};
 
 #[derive(thiserror::Error, Debug)]
 enum ReportError {
@@ -32,12 +54,48 @@ impl<T> From<SendError<T>> for ReportError {
     }
 }
 
-pub fn run(rx: Receiver<Report>, tx: Sender<Message>) {
+pub fn run(
+    rx: Receiver<Report>,
+    tx: Sender<Message>,
+    config: DiagnosticsConfig,
+    hints: HintsConfig,
+) {
     let _span = info_span!("report").entered();
 
+    let mut refreshes = 0;
+
     for msg in rx.iter() {
         let result = match msg {
-            Report::Plain(path, trace) => send_trace(&tx, &path, &trace),
+            Report::Plain(path, trace) => send_trace(&tx, &config, &path, &trace),
+            Report::Diagnostics(path, diags) => send_diagnostics(&tx, &path, diags),
+            Report::Document(id, path, trace, result_id) => {
+                send_document(&tx, &config, id, &path, &trace, result_id)
+            }
+            Report::Unchanged(id, result_id) => send_unchanged(&tx, id, result_id),
+            Report::Workspace(id, files) => send_workspace(&tx, &config, id, files),
+            Report::Functions(id, files) => send_functions(&tx, id, files),
+            Report::Hover(id, path, hovered) => send_hover(&tx, id, &path, hovered),
+            Report::InlayHints(id, path, traces, lines) => {
+                send_inlay_hints(&tx, &hints, id, &path, &traces, lines)
+            }
+            Report::CodeLens(id, path, traces) => send_code_lenses(&tx, id, &path, &traces),
+            Report::CodeActions(id, actions) => send_code_actions(&tx, &config, id, actions),
+            Report::CoveredBy(id, tests) => {
+                let res = Response::new_ok(id, tests);
+                tx.send(Message::Response(res)).map_err(ReportError::from)
+            }
+            Report::Done(id) => {
+                let res = Response::new_ok(id, ());
+                tx.send(Message::Response(res)).map_err(ReportError::from)
+            }
+            Report::SemanticTokens(id, path, traces, ignored, range) => {
+                send_semantic_tokens(&tx, &hints, id, &path, &traces, &ignored, range)
+            }
+            Report::Refresh => {
+                refreshes += 1;
+                send_refresh(&tx, refreshes)
+            }
+            Report::Failed(id, message) => send_failed(&tx, id, message),
             Report::Message(ty, message) => send_message(&tx, ty, message),
             Report::Exit(id) => {
                 let res = Response::new_ok(id, ());
@@ -57,54 +115,731 @@ pub fn run(rx: Receiver<Report>, tx: Sender<Message>) {
     }
 }
 
-fn send_trace(tx: &Sender<Message>, path: &Path, traces: &[Trace]) -> Result<(), ReportError> {
+fn send_trace(
+    tx: &Sender<Message>,
+    config: &DiagnosticsConfig,
+    path: &Path,
+    traces: &[Trace],
+) -> Result<(), ReportError> {
+    let diag = diagnostics(config, path, traces)?;
+
+    send_diagnostics(tx, path, diag)
+}
+
+fn send_diagnostics(
+    tx: &Sender<Message>,
+    path: &Path,
+    diag: Vec<Diagnostic>,
+) -> Result<(), ReportError> {
+    let uri = Url::parse(&format!("file://{}", path.display()))?;
+
+    tx.send(Message::Notification(Notification::new(
+        <PublishDiagnostics as lsp_types::notification::Notification>::METHOD.to_string(),
+        PublishDiagnosticsParams {
+            uri,
+            diagnostics: diag,
+            version: None,
+        },
+    )))?;
+
+    Ok(())
+}
+
+fn send_document(
+    tx: &Sender<Message>,
+    config: &DiagnosticsConfig,
+    id: RequestId,
+    path: &Path,
+    traces: &[Trace],
+    result_id: Option<String>,
+) -> Result<(), ReportError> {
+    let items = match diagnostics(config, path, traces) {
+        Ok(items) => items,
+        Err(error) => {
+            send_failed(tx, id, error.to_string())?;
+            return Err(error);
+        }
+    };
+
+    let report = DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(
+        RelatedFullDocumentDiagnosticReport {
+            related_documents: None,
+            full_document_diagnostic_report: FullDocumentDiagnosticReport { result_id, items },
+        },
+    ));
+
+    tx.send(Message::Response(Response::new_ok(id, report)))?;
+
+    Ok(())
+}
+
+fn send_unchanged(
+    tx: &Sender<Message>,
+    id: RequestId,
+    result_id: String,
+) -> Result<(), ReportError> {
+    let report = DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Unchanged(
+        RelatedUnchangedDocumentDiagnosticReport {
+            related_documents: None,
+            unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport { result_id },
+        },
+    ));
+
+    tx.send(Message::Response(Response::new_ok(id, report)))?;
+
+    Ok(())
+}
+
+fn send_workspace(
+    tx: &Sender<Message>,
+    config: &DiagnosticsConfig,
+    id: RequestId,
+    files: Vec<FileReport>,
+) -> Result<(), ReportError> {
+    let mut items = Vec::with_capacity(files.len());
+
+    for file in files {
+        let item = match file {
+            FileReport::Full(path, traces, result_id) => {
+                let diagnostics = match diagnostics(config, &path, &traces) {
+                    Ok(diagnostics) => diagnostics,
+                    Err(error) => {
+                        error!(%error, "failed to build diagnostics");
+                        continue;
+                    }
+                };
+
+                WorkspaceDocumentDiagnosticReport::Full(WorkspaceFullDocumentDiagnosticReport {
+                    uri: Url::parse(&format!("file://{}", path.display()))?,
+                    version: None,
+                    full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                        result_id: Some(result_id),
+                        items: diagnostics,
+                    },
+                })
+            }
+
+            FileReport::Unchanged(path, result_id) => WorkspaceDocumentDiagnosticReport::Unchanged(
+                WorkspaceUnchangedDocumentDiagnosticReport {
+                    uri: Url::parse(&format!("file://{}", path.display()))?,
+                    version: None,
+                    unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport {
+                        result_id,
+                    },
+                },
+            ),
+        };
+
+        items.push(item);
+    }
+
+    let report = WorkspaceDiagnosticReportResult::Report(WorkspaceDiagnosticReport { items });
+
+    tx.send(Message::Response(Response::new_ok(id, report)))?;
+
+    Ok(())
+}
+
+fn send_functions(
+    tx: &Sender<Message>,
+    id: RequestId,
+    files: Vec<(PathBuf, Vec<Trace>)>,
+) -> Result<(), ReportError> {
+    let mut items = Vec::new();
+
+    for (path, traces) in files {
+        let content = match std::fs::read(&path) {
+            Ok(content) => content,
+            Err(error) => {
+                error!(%error, path = %path.display(), "failed to read source");
+                continue;
+            }
+        };
+
+        let functions = match summary::functions(&content, &summary::lines(&traces)) {
+            Ok(functions) => functions,
+            Err(error) => {
+                error!(%error, path = %path.display(), "failed to find functions");
+                continue;
+            }
+        };
+
+        let uri = Url::parse(&format!("file://{}", path.display()))?;
+        for coverage in functions {
+            let function = &coverage.function;
+
+            items.push(FunctionCoverage {
+                uri: uri.clone(),
+                name: function.qualified_name(),
+                range: range(function.start, function.end),
+                selection_range: range(function.start, function.name_end),
+                covered: coverage.totals.covered,
+                total: coverage.totals.total,
+                percent: coverage.totals.percent(),
+                reported_name: coverage.reported,
+            });
+        }
+    }
+
+    tx.send(Message::Response(Response::new_ok(id, items)))?;
+
+    Ok(())
+}
+
+fn send_hover(
+    tx: &Sender<Message>,
+    id: RequestId,
+    path: &Path,
+    hovered: Option<Hovered>,
+) -> Result<(), ReportError> {
+    let hover = match hovered {
+        Some(hovered) => hover(path, &hovered)?,
+        None => None,
+    };
+
+    tx.send(Message::Response(Response::new_ok(id, hover)))?;
+
+    Ok(())
+}
+
+/// "×1523" at the end of every line in view that the config shows
+fn send_inlay_hints(
+    tx: &Sender<Message>,
+    config: &HintsConfig,
+    id: RequestId,
+    path: &Path,
+    traces: &[Trace],
+    (first, last): (u32, u32),
+) -> Result<(), ReportError> {
+    let mut hints = Vec::new();
+
+    if !traces.is_empty() {
+        let content =
+            std::fs::read(path).map_err(|e| ReportError::FailedFileRead(path.to_path_buf(), e))?;
+        let line_slices = LineSlice::build(&content);
+
+        for (number, line) in summary::lines(traces).range(first..=last) {
+            let Some(hits) = line.hits.filter(|hits| config.shows(*hits)) else {
+                continue;
+            };
+            let Some(row) = number.checked_sub(1) else {
+                continue;
+            };
+            let Some(line_slice) = line_slices.get(row as usize) else {
+                continue;
+            };
+
+            hints.push(InlayHint {
+                position: Position::new(row, (line_slice.end - line_slice.start) as u32),
+                label: InlayHintLabel::String(format!("×{hits}")),
+                kind: None,
+                text_edits: None,
+                tooltip: None,
+                padding_left: Some(true),
+                padding_right: None,
+                data: None,
+            });
+        }
+    }
+
+    tx.send(Message::Response(Response::new_ok(id, hints)))?;
+
+    Ok(())
+}
+
+fn send_semantic_tokens(
+    tx: &Sender<Message>,
+    config: &HintsConfig,
+    id: RequestId,
+    path: &Path,
+    traces: &[Trace],
+    ignored: &[u32],
+    range: Option<(u32, u32)>,
+) -> Result<(), ReportError> {
+    let mut data = Vec::new();
+
+    if !traces.is_empty() || !ignored.is_empty() {
+        let content =
+            std::fs::read(path).map_err(|e| ReportError::FailedFileRead(path.to_path_buf(), e))?;
+
+        data = heatmap::tokens(
+            &content,
+            &summary::lines(traces),
+            ignored,
+            config.hot,
+            range,
+        );
+    }
+
+    let tokens = SemanticTokens {
+        result_id: None,
+        data,
+    };
+
+    tx.send(Message::Response(Response::new_ok(id, tokens)))?;
+
+    Ok(())
+}
+
+fn send_code_actions(
+    tx: &Sender<Message>,
+    config: &DiagnosticsConfig,
+    id: RequestId,
+    actions: Option<Actions>,
+) -> Result<(), ReportError> {
+    let mut items = Vec::new();
+
+    if let Some(actions) = actions {
+        let content = std::fs::read(&actions.path)
+            .map_err(|e| ReportError::FailedFileRead(actions.path.clone(), e))?;
+
+        match ignore::suggest(&content, &actions.relative, actions.point) {
+            Ok(suggestions) => {
+                for suggestion in suggestions {
+                    items.push(CodeActionOrCommand::CodeAction(ignore_action(
+                        &actions.ignore_file,
+                        suggestion,
+                    )?));
+                }
+            }
+            Err(error) => {
+                error!(%error, path = %actions.path.display(), "failed to suggest ignore rules")
+            }
+        }
+
+        match syntax::attribute_insertions(&content, actions.point, &config.attribute) {
+            Ok(insertions) => {
+                let uri = Url::parse(&format!("file://{}", actions.path.display()))?;
+
+                for insertion in insertions {
+                    let at = Position::new(insertion.at.row as u32, insertion.at.column as u32);
+                    let edit = TextEdit::new(Range::new(at, at), insertion.text);
+
+                    items.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: insertion.title,
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        edit: Some(WorkspaceEdit {
+                            changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                            ..WorkspaceEdit::default()
+                        }),
+                        ..CodeAction::default()
+                    }));
+                }
+            }
+            Err(error) => {
+                error!(%error, path = %actions.path.display(), "failed to find items to exclude")
+            }
+        }
+    }
+
+    tx.send(Message::Response(Response::new_ok(id, items)))?;
+
+    Ok(())
+}
+
+/// appends the rule to the ignore file, creating it if need be, then has the server pick it up
+fn ignore_action(file: &Path, suggestion: Suggestion) -> Result<CodeAction, ReportError> {
+    let uri = Url::parse(&format!("file://{}", file.display()))?;
+    let existing = std::fs::read_to_string(file).ok();
+
+    let end = existing.as_deref().unwrap_or_default();
+    let position = Position::new(
+        end.matches('\n').count() as u32,
+        end.rsplit('\n').next().unwrap_or_default().len() as u32,
+    );
+
+    // rules are kept apart by a blank line
+    let separator = match end {
+        "" => "",
+        end if end.ends_with("\n\n") => "",
+        end if end.ends_with('\n') => "\n",
+        _ => "\n\n",
+    };
+
+    let insert = TextEdit::new(
+        Range::new(position, position),
+        format!("{separator}{}", suggestion.rule),
+    );
+
+    let edit = match existing {
+        Some(_) => WorkspaceEdit {
+            changes: Some(HashMap::from([(uri, vec![insert])])),
+            ..WorkspaceEdit::default()
+        },
+        None => WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(vec![
+                DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+                    uri: uri.clone(),
+                    options: None,
+                    annotation_id: None,
+                })),
+                DocumentChangeOperation::Edit(TextDocumentEdit {
+                    text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+                    edits: vec![OneOf::Left(insert)],
+                }),
+            ])),
+            ..WorkspaceEdit::default()
+        },
+    };
+
+    Ok(CodeAction {
+        title: suggestion.title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(edit),
+        command: Some(Command::new(
+            "Reload ignore rules".to_string(),
+            lsp_ext::ADD_IGNORE.to_string(),
+            Some(vec![suggestion.rule.into()]),
+        )),
+        ..CodeAction::default()
+    })
+}
+
+/// coverage above every function, and a run button above every test
+fn send_code_lenses(
+    tx: &Sender<Message>,
+    id: RequestId,
+    path: &Path,
+    traces: &[Trace],
+) -> Result<(), ReportError> {
+    let content =
+        std::fs::read(path).map_err(|e| ReportError::FailedFileRead(path.to_path_buf(), e))?;
+
+    let lines = summary::lines(traces);
+    let mut lenses = Vec::new();
+
+    let tree = match syntax::parse(&content) {
+        Ok(tree) => tree,
+        Err(error) => {
+            send_failed(tx, id, error.to_string())?;
+            return Ok(());
+        }
+    };
+
+    let module_path = syntax::module_path(path);
+
+    for function in syntax::functions(&tree, &content) {
+        let first = function.start.row as u32 + 1;
+        let last = function.end.row as u32 + 1;
+        let range = range(function.start, function.name_end);
+
+        if function.test {
+            // the full name libtest knows the test by, so exactly this test runs
+            let filter = module_path
+                .iter()
+                .chain(&function.modules)
+                .chain([&function.name])
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("::");
+
+            lenses.push(CodeLens {
+                range,
+                command: Some(Command::new(
+                    "Run with coverage".to_string(),
+                    lsp_ext::RUN_TEST.to_string(),
+                    Some(vec![filter.into(), true.into()]),
+                )),
+                data: None,
+            });
+            continue;
+        }
+
+        let totals = summary::Totals::of(lines.range(first..=last).map(|(_, line)| line));
+        if totals.total == 0 {
+            continue;
+        }
+
+        let uncovered = totals.total - totals.covered;
+        let title = format!(
+            "{:.0}% covered · {uncovered} uncovered line{}",
+            totals.percent(),
+            if uncovered == 1 { "" } else { "s" }
+        );
+
+        // clicking the coverage of a function goes back to the full coverage
+        lenses.push(CodeLens {
+            range,
+            command: Some(Command::new(title, lsp_ext::SHOW_ALL.to_string(), None)),
+            data: None,
+        });
+    }
+
+    tx.send(Message::Response(Response::new_ok(id, lenses)))?;
+
+    Ok(())
+}
+
+/// markdown about the hits on a line and the function around it, `None` when there is nothing to say
+fn hover(path: &Path, hovered: &Hovered) -> Result<Option<Hover>, ReportError> {
+    let content =
+        std::fs::read(path).map_err(|e| ReportError::FailedFileRead(path.to_path_buf(), e))?;
+
+    let lines = summary::lines(&hovered.traces);
+    let mut parts = Vec::new();
+
+    if let Some(line) = lines.get(&hovered.line) {
+        parts.push(match line.hits {
+            Some(0) => "**never hit**".to_string(),
+            Some(1) => "**hit once**".to_string(),
+            Some(hits) => format!("**hit {hits} times**"),
+            None if line.covered => "**branches taken**".to_string(),
+            None => "**branches never taken**".to_string(),
+        });
+
+        for untaken in &line.untaken {
+            parts.push(format!("branch never taken: {untaken}"));
+        }
+    }
+
+    if !hovered.tests.is_empty() {
+        const SHOWN: usize = 10;
+
+        let mut tests = hovered
+            .tests
+            .iter()
+            .take(SHOWN)
+            .map(|test| format!("`{test}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if hovered.tests.len() > SHOWN {
+            tests.push_str(&format!(" and {} more", hovered.tests.len() - SHOWN));
+        }
+
+        parts.push(format!("covered by {tests}"));
+    }
+
+    if let Some(rule) = &hovered.ignored {
+        parts.push(format!("ignored by the tarballin-ignore rule {rule}"));
+    }
+
+    let functions = match summary::functions(&content, &lines) {
+        Ok(functions) => functions,
+        Err(error) => {
+            error!(%error, path = %path.display(), "failed to find functions");
+            Vec::new()
+        }
+    };
+
+    // the innermost function around the line
+    let row = (hovered.line as usize).checked_sub(1);
+    let enclosing = functions
+        .iter()
+        .filter(|f| row.is_some_and(|row| f.function.start.row <= row && row <= f.function.end.row))
+        .min_by_key(|f| f.function.end.row - f.function.start.row);
+
+    if let Some(coverage) = enclosing {
+        let totals = &coverage.totals;
+        parts.push(format!(
+            "`{}`: {}/{} lines covered ({:.2}%)",
+            coverage.function.qualified_name(),
+            totals.covered,
+            totals.total,
+            totals.percent()
+        ));
+    }
+
+    if parts.is_empty() {
+        return Ok(None);
+    }
+
+    parts.push(match hovered.collected {
+        Some(collected) => format!(
+            "coverage generation {}, collected {}",
+            hovered.generation,
+            timestamp(collected)
+        ),
+        None => format!("coverage generation {}", hovered.generation),
+    });
+
+    Ok(Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: parts.join("\n\n"),
+        }),
+        range: None,
+    }))
+}
+
+/// "2024-03-01 12:30:00 UTC"
+fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    // days to a civil date, after Howard Hinnant's algorithm
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+
+    let rest = secs % 86400;
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        rest / 3600,
+        rest / 60 % 60,
+        rest % 60
+    )
+}
+
+fn send_refresh(tx: &Sender<Message>, n: usize) -> Result<(), ReportError> {
+    let id = RequestId::from(format!("tarballin-refresh-{n}"));
+
+    tx.send(Message::Request(Request::new(
+        id,
+        <WorkspaceDiagnosticRefresh as lsp_types::request::Request>::METHOD.to_string(),
+        (),
+    )))?;
+
+    Ok(())
+}
+
+fn send_failed(tx: &Sender<Message>, id: RequestId, message: String) -> Result<(), ReportError> {
+    tx.send(Message::Response(Response::new_err(
+        id,
+        ErrorCode::InternalError as i32,
+        message,
+    )))?;
+
+    Ok(())
+}
+
+fn diagnostics(
+    config: &DiagnosticsConfig,
+    path: &Path,
+    traces: &[Trace],
+) -> Result<Vec<Diagnostic>, ReportError> {
     let content =
         std::fs::read(path).map_err(|e| ReportError::FailedFileRead(path.to_path_buf(), e))?;
 
     let line_slices = LineSlice::build(&content);
 
     let mut diag = Vec::new();
-    for trace in traces {
-        if trace.stats.line == 0 {
-            let line = trace.line.saturating_sub(1);
+    for uncovered in group::uncovered(&content, &summary::lines(traces), config.group) {
+        let message = match &uncovered.function {
+            Some(name) => format!("`{name}` not covered by tests"),
+            None => "not covered by tests".to_string(),
+        };
+
+        diag.push(Diagnostic {
+            range: range(uncovered.start, uncovered.end),
+            severity: Some(DiagnosticSeverity::WARNING),
+            source: Some("lsp-tarpaulin".to_string()),
+            message,
+            ..Diagnostic::default()
+        });
+    }
+
+    if config.functions {
+        match summary::functions(&content, &summary::lines(traces)) {
+            Ok(functions) => diag.extend(functions.iter().map(function_diagnostic)),
+            Err(error) => error!(%error, path = %path.display(), "failed to find functions"),
+        }
+    }
+
+    for trace in traces.iter().filter(|trace| trace.stats.covered()) {
+        let line = trace.line.saturating_sub(1);
+
+        let Some(line_slice) = line_slices.get(line as usize) else {
+            continue;
+        };
 
-            let line_slice = &line_slices[line as usize];
+        for span in &trace.uncovered {
+            let Some(range) = span_range(&content, line_slice, line, span) else {
+                continue;
+            };
 
             diag.push(Diagnostic {
-                range: Range::new(
-                    Position {
-                        line,
-                        character: (line_slice.begin - line_slice.start) as u32,
-                    },
-                    Position {
-                        line,
-                        character: (line_slice.end - line_slice.start) as u32,
-                    },
-                ),
+                range,
                 severity: Some(DiagnosticSeverity::WARNING),
-                code: None,
-                code_description: None,
+                code: Some(NumberOrString::String("region-not-covered".to_string())),
                 source: Some("lsp-tarpaulin".to_string()),
-                message: "not covered by tests".to_string(),
-                related_information: None,
-                tags: None,
-                data: None,
+                message: "region not covered by tests".to_string(),
+                ..Diagnostic::default()
             });
         }
+
+        let Some(untaken) = trace.stats.untaken() else {
+            continue;
+        };
+
+        diag.push(Diagnostic {
+            range: Range::new(
+                Position {
+                    line,
+                    character: (line_slice.begin - line_slice.start) as u32,
+                },
+                Position {
+                    line,
+                    character: (line_slice.end - line_slice.start) as u32,
+                },
+            ),
+            severity: Some(DiagnosticSeverity::INFORMATION),
+            code: Some(NumberOrString::String("branch-never-taken".to_string())),
+            code_description: None,
+            source: Some("lsp-tarpaulin".to_string()),
+            message: format!("branch never taken: {untaken}"),
+            related_information: None,
+            tags: None,
+            data: None,
+        });
     }
 
-    let uri = Url::parse(&format!("file://{}", path.display()))?;
+    Ok(diag)
+}
 
-    tx.send(Message::Notification(Notification::new(
-        <PublishDiagnostics as lsp_types::notification::Notification>::METHOD.to_string(),
-        PublishDiagnosticsParams {
-            uri,
-            diagnostics: diag,
-            version: None,
-        },
-    )))?;
+/// "fn parse: 3/12 lines covered (25%)" on the signature
+fn function_diagnostic(coverage: &summary::FunctionCoverage) -> Diagnostic {
+    let function = &coverage.function;
+    let totals = &coverage.totals;
 
-    Ok(())
+    Diagnostic {
+        range: range(function.start, function.name_end),
+        severity: Some(DiagnosticSeverity::INFORMATION),
+        code: Some(NumberOrString::String("function-coverage".to_string())),
+        source: Some("lsp-tarpaulin".to_string()),
+        message: format!(
+            "fn {}: {}/{} lines covered ({:.0}%)",
+            function.qualified_name(),
+            totals.covered,
+            totals.total,
+            totals.percent()
+        ),
+        ..Diagnostic::default()
+    }
+}
+
+fn range(start: Point, end: Point) -> Range {
+    Range::new(
+        Position::new(start.row as u32, start.column as u32),
+        Position::new(end.row as u32, end.column as u32),
+    )
+}
+
+/// the columns of an uncovered region clipped to its line, `None` when only whitespace is left
+fn span_range(content: &[u8], line_slice: &LineSlice, line: u32, span: &Span) -> Option<Range> {
+    let len = line_slice.end - line_slice.start;
+    let start = (span.start.saturating_sub(1) as usize).min(len);
+    let end = match span.end {
+        Some(end) => (end.saturating_sub(1) as usize).min(len),
+        None => len,
+    };
+
+    let text = content.get(line_slice.start + start..line_slice.start + end)?;
+    if text.iter().all(u8::is_ascii_whitespace) {
+        return None;
+    }
+
+    Some(Range::new(
+        Position::new(line, start as u32),
+        Position::new(line, end as u32),
+    ))
 }
 
 fn send_message(
@@ -119,3 +854,23 @@ fn send_message(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01 00:00:00 UTC");
+        assert_eq!(
+            timestamp(UNIX_EPOCH + Duration::from_secs(1709296200)),
+            "2024-03-01 12:30:00 UTC"
+        );
+        assert_eq!(
+            timestamp(UNIX_EPOCH + Duration::from_secs(951825599)),
+            "2000-02-29 11:59:59 UTC"
+        );
+    }
+}